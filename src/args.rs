@@ -0,0 +1,60 @@
+use crate::mode::Action;
+
+use std::collections::HashSet;
+
+/// An argument of a popup: the action toggling it, its description and the
+/// flag passed to git. A flag ending in `=` takes a value, which is appended
+/// to it when enabled.
+pub type PopupArg = (Action, &'static str, &'static str);
+
+/// The enabled argument of `flag`, with its value if it takes one
+pub fn enabled_flag<'a>(enabled: &'a HashSet<String>, flag: &str) -> Option<&'a String> {
+    if flag.ends_with('=') {
+        enabled.iter().find(|f| f.starts_with(flag))
+    } else {
+        enabled.get(flag)
+    }
+}
+
+/// Toggles the argument of `args` bound to `action`. Returns false if
+/// `action` doesn't toggle any of them. Arguments taking a value are only
+/// disabled here, they're enabled once the value is known.
+pub fn toggle_arg(enabled: &mut HashSet<String>, args: &[PopupArg], action: Action) -> bool {
+    match args.iter().find(|(a, _, _)| *a == action) {
+        Some((_, _, flag)) => {
+            match enabled_flag(enabled, flag).cloned() {
+                Some(on) => {
+                    enabled.remove(&on);
+                }
+                None if flag.ends_with('=') => {}
+                None => {
+                    enabled.insert(String::from(*flag));
+                }
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// The enabled flags of `args`, in the order they are listed
+pub fn enabled_args(args: &[PopupArg], enabled: &HashSet<String>) -> Vec<String> {
+    args.iter()
+        .filter_map(|(_, _, flag)| enabled_flag(enabled, flag).cloned())
+        .collect()
+}
+
+/// Like `toggle_arg` for arguments only one of which can be enabled, the
+/// one just enabled disables the others
+pub fn toggle_exclusive_arg(
+    enabled: &mut HashSet<String>,
+    args: &[PopupArg],
+    action: Action,
+) -> bool {
+    if !toggle_arg(enabled, args, action) {
+        return false;
+    }
+    let flag = args.iter().find(|(a, _, _)| *a == action).map(|a| a.2);
+    enabled.retain(|f| Some(f.as_str()) == flag);
+    true
+}
@@ -1,41 +1,617 @@
+use crate::key::{chord_to_string, parse_chord};
 use crate::mode::Action;
+use crate::theme::{Role, Style, Theme, BUNDLED_THEMES};
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 
 pub struct Config {
     pub stage_mode_key_map: Vec<(String, Action)>,
     pub commit_mode_key_map: Vec<(String, Action)>,
+    pub commit_msg_mode_key_map: Vec<(String, Action)>,
+    pub commit_pick_mode_key_map: Vec<(String, Action)>,
+    pub confirm_mode_key_map: Vec<(String, Action)>,
+    pub pull_mode_key_map: Vec<(String, Action)>,
+    pub fetch_mode_key_map: Vec<(String, Action)>,
+    pub push_mode_key_map: Vec<(String, Action)>,
+    pub branch_mode_key_map: Vec<(String, Action)>,
+    pub stash_mode_key_map: Vec<(String, Action)>,
+    pub log_mode_key_map: Vec<(String, Action)>,
+    pub revision_mode_key_map: Vec<(String, Action)>,
+    pub rebase_mode_key_map: Vec<(String, Action)>,
+    pub cherry_pick_mode_key_map: Vec<(String, Action)>,
+    pub revert_mode_key_map: Vec<(String, Action)>,
+    pub reset_mode_key_map: Vec<(String, Action)>,
+    pub theme: Theme,
+}
+
+/// A line of the config file that couldn't be used
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
 }
 
 impl Config {
+    /// The default bindings
     pub fn new() -> Config {
         Config {
-            stage_mode_key_map: vec![
+            stage_mode_key_map: [
                 ("j", Action::CursorDown),
                 ("k", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<Up>", Action::CursorUp),
+                ("<C-n>", Action::CursorDown),
+                ("<C-p>", Action::CursorUp),
+                ("gg", Action::CursorBufferStart),
+                ("G", Action::CursorBufferEnd),
+                ("]", Action::NextSection),
+                ("[", Action::PrevSection),
                 ("q", Action::Exit),
                 ("s", Action::StageFile),
                 ("S", Action::StageAllFiles),
                 ("u", Action::UnstageFile),
+                ("<Tab>", Action::ToggleExpand),
+                ("v", Action::ToggleVisualSelect),
+                ("x", Action::Discard),
+                ("o", Action::TakeOurs),
+                ("t", Action::TakeTheirs),
+                ("m", Action::Mergetool),
+                ("C", Action::ContinueOperation),
+                ("X", Action::SkipOperation),
+                ("A", Action::AbortOperation),
                 ("c", Action::OpenCommitMode),
                 ("?", Action::OpenHelpMode),
-                ("p", Action::Push),
+                ("p", Action::OpenPushMode),
+                ("F", Action::OpenPullMode),
+                ("f", Action::OpenFetchMode),
+                ("b", Action::OpenBranchMode),
+                ("l", Action::OpenLogMode),
+                ("z", Action::OpenStashMode),
+                ("R", Action::OpenResetMode),
+                ("<C-g>", Action::CancelJob),
                 ("<Esc>", Action::Exit),
             ]
             .iter()
             .map(|(s, a)| (String::from(*s), *a))
             .collect(),
-            commit_mode_key_map: vec![
+            commit_mode_key_map: [
                 ("-a", Action::ToggleCommitStageAll),
                 ("-e", Action::ToggleCommitAllowEmpty),
                 ("-v", Action::ToggleCommitVerbose),
                 ("-n", Action::ToggleCommitDisableHooks),
                 ("-R", Action::ToggleCommitResetAuthor),
                 ("c", Action::OpenCommitMsgMode),
+                ("C", Action::CommitInEditor),
+                ("a", Action::CommitAmend),
+                ("e", Action::CommitExtend),
+                ("w", Action::CommitReword),
+                ("f", Action::CommitFixup),
+                ("s", Action::CommitSquash),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            commit_msg_mode_key_map: [
+                ("<C-c><C-c>", Action::ConfirmCommitMsg),
+                ("<Esc>", Action::Exit),
+                ("<Left>", Action::CursorLeft),
+                ("<Right>", Action::CursorRight),
+                ("<Up>", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<C-b>", Action::CursorLeft),
+                ("<C-f>", Action::CursorRight),
+                ("<C-p>", Action::CursorUp),
+                ("<C-n>", Action::CursorDown),
+                ("<Home>", Action::CursorLineStart),
+                ("<End>", Action::CursorLineEnd),
+                ("<C-a>", Action::CursorLineStart),
+                ("<C-e>", Action::CursorLineEnd),
+                ("<M-f>", Action::CursorWordForward),
+                ("<M-b>", Action::CursorWordBackward),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            commit_pick_mode_key_map: [
+                ("j", Action::CursorDown),
+                ("k", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<Up>", Action::CursorUp),
+                ("gg", Action::CursorBufferStart),
+                ("G", Action::CursorBufferEnd),
+                ("<CR>", Action::Confirm),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            confirm_mode_key_map: [
+                ("y", Action::Confirm),
+                ("n", Action::Exit),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            pull_mode_key_map: [
+                ("-r", Action::TogglePullRebase),
+                ("-f", Action::TogglePullFfOnly),
+                ("-a", Action::TogglePullAutostash),
+                ("p", Action::PullUpstream),
+                ("e", Action::PullElsewhere),
                 ("q", Action::Exit),
                 ("<Esc>", Action::Exit),
             ]
             .iter()
             .map(|(s, a)| (String::from(*s), *a))
             .collect(),
+            fetch_mode_key_map: [
+                ("-p", Action::ToggleFetchPrune),
+                ("-a", Action::ToggleFetchAll),
+                ("-t", Action::ToggleFetchTags),
+                ("p", Action::FetchUpstream),
+                ("e", Action::FetchElsewhere),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            push_mode_key_map: [
+                ("-f", Action::TogglePushForceWithLease),
+                ("-u", Action::TogglePushSetUpstream),
+                ("-t", Action::TogglePushTags),
+                ("-n", Action::TogglePushDisableHooks),
+                ("p", Action::Push),
+                ("e", Action::PushElsewhere),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            branch_mode_key_map: [
+                ("j", Action::CursorDown),
+                ("k", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<Up>", Action::CursorUp),
+                ("gg", Action::CursorBufferStart),
+                ("G", Action::CursorBufferEnd),
+                ("b", Action::CheckoutBranch),
+                ("c", Action::CreateBranch),
+                ("m", Action::RenameBranch),
+                ("x", Action::DeleteBranch),
+                ("l", Action::OpenLogMode),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            stash_mode_key_map: [
+                ("-u", Action::ToggleStashIncludeUntracked),
+                ("-k", Action::ToggleStashKeepIndex),
+                ("-a", Action::ToggleStashAll),
+                ("z", Action::Stash),
+                ("a", Action::StashApply),
+                ("p", Action::StashPop),
+                ("k", Action::StashDrop),
+                ("v", Action::StashShow),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            log_mode_key_map: [
+                ("j", Action::CursorDown),
+                ("k", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<Up>", Action::CursorUp),
+                ("<C-n>", Action::CursorDown),
+                ("<C-p>", Action::CursorUp),
+                ("gg", Action::CursorBufferStart),
+                ("G", Action::CursorBufferEnd),
+                ("<CR>", Action::ShowCommit),
+                ("-g", Action::ToggleLogGraph),
+                ("v", Action::ToggleVisualSelect),
+                ("r", Action::RebaseInteractive),
+                ("A", Action::OpenCherryPickMode),
+                ("V", Action::OpenRevertMode),
+                ("R", Action::OpenResetMode),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            revision_mode_key_map: [
+                ("j", Action::CursorDown),
+                ("k", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<Up>", Action::CursorUp),
+                ("<C-n>", Action::CursorDown),
+                ("<C-p>", Action::CursorUp),
+                ("gg", Action::CursorBufferStart),
+                ("G", Action::CursorBufferEnd),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            rebase_mode_key_map: [
+                ("j", Action::CursorDown),
+                ("k", Action::CursorUp),
+                ("<Down>", Action::CursorDown),
+                ("<Up>", Action::CursorUp),
+                ("gg", Action::CursorBufferStart),
+                ("G", Action::CursorBufferEnd),
+                ("p", Action::RebasePick),
+                ("r", Action::RebaseReword),
+                ("e", Action::RebaseEdit),
+                ("s", Action::RebaseSquash),
+                ("f", Action::RebaseFixup),
+                ("d", Action::RebaseDrop),
+                ("J", Action::RebaseMoveDown),
+                ("K", Action::RebaseMoveUp),
+                ("<CR>", Action::Confirm),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            cherry_pick_mode_key_map: [
+                ("-x", Action::ToggleCherryPickReference),
+                ("-n", Action::ToggleCherryPickNoCommit),
+                ("-m", Action::ToggleCherryPickMainline),
+                ("-e", Action::ToggleCherryPickEdit),
+                ("A", Action::CherryPick),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            revert_mode_key_map: [
+                ("-n", Action::ToggleRevertNoCommit),
+                ("-m", Action::ToggleRevertMainline),
+                ("-e", Action::ToggleRevertEdit),
+                ("V", Action::Revert),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            reset_mode_key_map: [
+                ("-s", Action::ToggleResetSoft),
+                ("-m", Action::ToggleResetMixed),
+                ("-h", Action::ToggleResetHard),
+                ("-k", Action::ToggleResetKeep),
+                ("R", Action::Reset),
+                ("e", Action::ResetElsewhere),
+                ("q", Action::Exit),
+                ("<Esc>", Action::Exit),
+            ]
+            .iter()
+            .map(|(s, a)| (String::from(*s), *a))
+            .collect(),
+            theme: Theme::dark(),
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/vrgit/config.toml`, falling back on `~/.config`
+    /// like the XDG spec says.
+    pub fn path() -> Option<PathBuf> {
+        let config_home = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("vrgit").join("config.toml"))
+    }
+
+    /// The defaults with the user's config file, if there is one, merged
+    /// over them. Anything wrong with the file is returned as messages
+    /// pointing at the offending lines, the rest of it still applies.
+    pub fn load() -> (Config, Vec<String>) {
+        let mut config = Config::new();
+        let path = match Config::path() {
+            Some(path) => path,
+            None => return (config, vec![]),
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return (config, vec![]),
+            Err(e) => return (config, vec![format!("{}: {}", path.display(), e)]),
+        };
+        let errors = config
+            .apply(&text)
+            .iter()
+            .map(|e| format!("{}:{}: {}", path.display(), e.line, e.message))
+            .collect();
+        (config, errors)
+    }
+
+    /// Merges the bindings of a config file over the current ones. The file
+    /// is a subset of TOML, a table per mode mapping chords to the names of
+    /// actions:
+    ///
+    /// ```toml
+    /// [stage]
+    /// "<C-n>" = "CursorDown"
+    /// ```
+    ///
+    /// A chord bound in the file replaces whatever it was bound to before.
+    /// Colors are set in a `[theme]` table, see `Config::apply_theme`.
+    pub fn apply(&mut self, text: &str) -> Vec<ConfigError> {
+        let mut errors = vec![];
+        // `None` before the first table and inside unknown ones
+        let mut table: Option<String> = None;
+        let mut in_table = false;
+
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| ConfigError {
+                line: i + 1,
+                message,
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                in_table = true;
+                let name = match header.strip_suffix(']') {
+                    Some(name) => name.trim(),
+                    None => {
+                        errors.push(error(String::from("unclosed table header")));
+                        table = None;
+                        continue;
+                    }
+                };
+                if name == "theme" || self.key_map_mut(name).is_some() {
+                    table = Some(String::from(name));
+                } else {
+                    errors.push(error(format!(
+                        "unknown mode \"{}\", expected theme or one of {}",
+                        name,
+                        MODES.join(", ")
+                    )));
+                    table = None;
+                }
+                continue;
+            }
+
+            let (chord, action) = match find_unquoted(line, '=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => {
+                    errors.push(error(String::from(
+                        "expected a binding like `\"<C-n>\" = \"CursorDown\"`",
+                    )));
+                    continue;
+                }
+            };
+            let chord = match parse_key(chord) {
+                Ok(chord) => chord,
+                Err(message) => {
+                    errors.push(error(message));
+                    continue;
+                }
+            };
+            let action = match parse_string(action) {
+                Ok(action) => action,
+                Err(message) => {
+                    errors.push(error(message));
+                    continue;
+                }
+            };
+            if table.as_deref() == Some("theme") {
+                if let Err(message) = self.apply_theme(&chord, &action) {
+                    errors.push(error(message));
+                }
+                continue;
+            }
+            // Written the way the defaults are, so `<c-N>` replaces `<C-n>`
+            let chord = match parse_chord(&chord) {
+                Ok(keys) => chord_to_string(&keys),
+                Err(message) => {
+                    errors.push(error(message));
+                    continue;
+                }
+            };
+            let action = match Action::from_name(&action) {
+                Some(action) => action,
+                None => {
+                    errors.push(error(format!("unknown action \"{}\"", action)));
+                    continue;
+                }
+            };
+
+            match table.as_deref().and_then(|name| self.key_map_mut(name)) {
+                Some(map) => match map.iter_mut().find(|(c, _)| *c == chord) {
+                    Some(binding) => binding.1 = action,
+                    None => map.push((chord, action)),
+                },
+                // Unknown tables have been reported already
+                None if in_table => {}
+                None => errors.push(error(String::from(
+                    "bindings have to be inside a mode table like [stage]",
+                ))),
+            }
+        }
+
+        errors
+    }
+
+    /// Sets a line of the `[theme]` table. `base` picks one of the bundled
+    /// themes and replaces every color set before it, the other keys name
+    /// a role and its colors:
+    ///
+    /// ```toml
+    /// [theme]
+    /// base = "light"
+    /// header = "#005f87"
+    /// selected = "black on 153"
+    /// ```
+    fn apply_theme(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "base" {
+            self.theme = Theme::bundled(value).ok_or_else(|| {
+                format!(
+                    "unknown theme \"{}\", expected one of {}",
+                    value,
+                    BUNDLED_THEMES.join(", ")
+                )
+            })?;
+            return Ok(());
+        }
+
+        let role = Role::from_name(key).ok_or_else(|| {
+            let names: Vec<&str> = Role::ALL.iter().map(|r| r.name()).collect();
+            format!(
+                "unknown theme role \"{}\", expected base or one of {}",
+                key,
+                names.join(", ")
+            )
+        })?;
+        self.theme.set(role, Style::parse(value)?);
+        Ok(())
+    }
+
+    fn key_map_mut(&mut self, mode: &str) -> Option<&mut Vec<(String, Action)>> {
+        match mode {
+            "stage" => Some(&mut self.stage_mode_key_map),
+            "commit" => Some(&mut self.commit_mode_key_map),
+            "commit_msg" => Some(&mut self.commit_msg_mode_key_map),
+            "commit_pick" => Some(&mut self.commit_pick_mode_key_map),
+            "confirm" => Some(&mut self.confirm_mode_key_map),
+            "pull" => Some(&mut self.pull_mode_key_map),
+            "fetch" => Some(&mut self.fetch_mode_key_map),
+            "push" => Some(&mut self.push_mode_key_map),
+            "branch" => Some(&mut self.branch_mode_key_map),
+            "stash" => Some(&mut self.stash_mode_key_map),
+            "log" => Some(&mut self.log_mode_key_map),
+            "revision" => Some(&mut self.revision_mode_key_map),
+            "rebase" => Some(&mut self.rebase_mode_key_map),
+            "cherry_pick" => Some(&mut self.cherry_pick_mode_key_map),
+            "revert" => Some(&mut self.revert_mode_key_map),
+            "reset" => Some(&mut self.reset_mode_key_map),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the tables in the config file, see `Config::key_map_mut`
+const MODES: &[&str] = &[
+    "stage",
+    "commit",
+    "commit_msg",
+    "commit_pick",
+    "confirm",
+    "pull",
+    "fetch",
+    "push",
+    "branch",
+    "stash",
+    "log",
+    "revision",
+    "rebase",
+    "cherry_pick",
+    "revert",
+    "reset",
+];
+
+/// `line` without a trailing `#` comment
+fn strip_comment(line: &str) -> &str {
+    match find_unquoted(line, '#') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+/// The byte index of the first `wanted` in `line` that isn't inside a
+/// quoted string
+fn find_unquoted(line: &str, wanted: char) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c == wanted => return Some(i),
+            _ => {}
+        }
+        escaped = false;
+    }
+    None
+}
+
+/// A key of a table, either a quoted string or a bare key
+fn parse_key(key: &str) -> Result<String, String> {
+    if key.starts_with('"') || key.starts_with('\'') {
+        return parse_string(key);
+    }
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(String::from(key))
+    } else {
+        Err(format!("key chord {} has to be quoted", key))
+    }
+}
+
+/// A basic `"..."` string with `\"` and `\\` escapes, or a literal `'...'`
+/// string
+fn parse_string(value: &str) -> Result<String, String> {
+    let unquoted = |q: char| {
+        value
+            .strip_prefix(q)?
+            .strip_suffix(q)
+            .filter(|_| value.len() > 1)
+    };
+
+    if let Some(literal) = unquoted('\'') {
+        return Ok(String::from(literal));
+    }
+    let basic = match unquoted('"') {
+        Some(basic) => basic,
+        None => return Err(format!("expected a quoted string, found {}", value)),
+    };
+
+    let mut string = String::new();
+    let mut chars = basic.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(e @ ('"' | '\\')) => string.push(e),
+                Some(e) => return Err(format!("unsupported escape \\{} in {}", e, value)),
+                None => return Err(format!("unterminated string {}", value)),
+            },
+            '"' => return Err(format!("unescaped `\"` in {}", value)),
+            c => string.push(c),
         }
     }
+    Ok(string)
 }
@@ -1,46 +1,351 @@
-#[cfg(target_os = "linux")]
-use ncurses::*;
-use pancurses::COLOR_PAIR;
+use pancurses::A_REVERSE;
 
+use crate::args::{enabled_args, enabled_flag, toggle_arg, toggle_exclusive_arg, PopupArg};
 use crate::config::*;
-use crate::git::Git;
+use crate::editor::wrap;
+use crate::git::{
+    ApplyTo, Branch, Commit, CommitDetails, Conflict, FetchResult, FileDiff, Git, GitResult, Job,
+    JobEvent, LogLine, Operation, Progress, PullResult, RepoStatus, Sequenced, Side, Stash,
+    StashApplied, StatusEntry, TodoLine, TodoVerb,
+};
+use crate::item::{find_cursor, item_at, section_jump, Item, Section};
+use crate::key::Key;
 use crate::mode::*;
+use crate::util::{char_width, display_width, follow};
 use crate::win::*;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
 use std::iter::zip;
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::Output;
 
 #[cfg(target_os = "windows")]
 pub fn set_escdelay(x: i32) {}
 
-#[derive(PartialEq, Debug)]
+/// How often a running job is checked on while no keys are pressed
+const JOB_POLL_MS: i32 = 100;
+
+/// A message shown at the bottom of the screen until the next key press
+enum Notification {
+    Info(String),
+    Error(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum OpenPanel {
-    STAGING,
-    COMMITING,
-    COMMITMSG,
-    HELP,
+    Staging,
+    Commiting,
+    CommitMsg,
+    Help,
+    Confirm,
+    Pulling,
+    Fetching,
+    Input,
+    Branches,
+    Pushing,
+    CommitPick,
+    Log,
+    Revision,
+    Stashing,
+    Rebase,
+    CherryPicking,
+    Reverting,
+    Resetting,
+}
+
+/// What the commit message editor or the commit picker is used for
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CommitKind {
+    Create,
+    Amend,
+    Reword,
+    Fixup,
+    Squash,
+}
+
+/// What a background job does, decides how its result is shown
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum JobKind {
+    Fetch,
+    Pull,
+    Push,
+}
+
+/// A git command running in the background
+struct RunningJob {
+    kind: JobKind,
+    job: Job,
+    /// The last progress update, until git prints something else
+    progress: Option<Progress>,
+    /// The last line git printed that wasn't a progress update
+    last_line: String,
 }
 
-#[allow(dead_code)]
-fn ctrl(key_code: i32) -> i32 {
-    key_code & 0x1f
+/// What the text entered in the input panel is used for
+#[derive(Clone, PartialEq, Debug)]
+enum InputPurpose {
+    PullFrom,
+    FetchFrom,
+    PushTo,
+    /// Holds the start point
+    CreateBranch(String),
+    /// Holds the current name
+    RenameBranch(String),
+    StashMessage,
+    ResetTo,
+    /// The parent number `--mainline` picks or reverts merges relative to
+    Mainline(Operation),
 }
 
-#[allow(dead_code)]
-fn ctrl_c(key: char) -> i32 {
-    ctrl(key as u8 as i32)
+const COMMIT_ARGS: &[PopupArg] = &[
+    (
+        Action::ToggleCommitStageAll,
+        "Stage all modified and deleted files",
+        "--all",
+    ),
+    (
+        Action::ToggleCommitAllowEmpty,
+        "Allow empty commit",
+        "--allow-empty",
+    ),
+    (
+        Action::ToggleCommitVerbose,
+        "Show diff of changes to be commited",
+        "--verbose",
+    ),
+    (
+        Action::ToggleCommitDisableHooks,
+        "Disable hooks",
+        "--no-verify",
+    ),
+    (
+        Action::ToggleCommitResetAuthor,
+        "Claim authorship and reset author date",
+        "--reset-author",
+    ),
+];
+
+const PULL_ARGS: &[PopupArg] = &[
+    (Action::TogglePullRebase, "Rebase local commits", "--rebase"),
+    (Action::TogglePullFfOnly, "Only fast-forward", "--ff-only"),
+    (
+        Action::TogglePullAutostash,
+        "Stash local changes around the pull",
+        "--autostash",
+    ),
+];
+
+const PUSH_ARGS: &[PopupArg] = &[
+    (
+        Action::TogglePushForceWithLease,
+        "Force with lease",
+        "--force-with-lease",
+    ),
+    (
+        Action::TogglePushSetUpstream,
+        "Set upstream",
+        "--set-upstream",
+    ),
+    (Action::TogglePushTags, "Include all tags", "--tags"),
+    (
+        Action::TogglePushDisableHooks,
+        "Disable hooks",
+        "--no-verify",
+    ),
+];
+
+const STASH_ARGS: &[PopupArg] = &[
+    (
+        Action::ToggleStashIncludeUntracked,
+        "Include untracked files",
+        "--include-untracked",
+    ),
+    (
+        Action::ToggleStashKeepIndex,
+        "Keep staged changes",
+        "--keep-index",
+    ),
+    (
+        Action::ToggleStashAll,
+        "Include untracked and ignored files",
+        "--all",
+    ),
+];
+
+const CHERRY_PICK_ARGS: &[PopupArg] = &[
+    (
+        Action::ToggleCherryPickReference,
+        "Reference the picked commit in the message",
+        "-x",
+    ),
+    (
+        Action::ToggleCherryPickNoCommit,
+        "Don't commit, only apply the changes",
+        "--no-commit",
+    ),
+    (
+        Action::ToggleCherryPickMainline,
+        "Pick merges relative to a parent",
+        "--mainline=",
+    ),
+    (Action::ToggleCherryPickEdit, "Edit the message", "--edit"),
+];
+
+const REVERT_ARGS: &[PopupArg] = &[
+    (
+        Action::ToggleRevertNoCommit,
+        "Don't commit, only revert the changes",
+        "--no-commit",
+    ),
+    (
+        Action::ToggleRevertMainline,
+        "Revert merges relative to a parent",
+        "--mainline=",
+    ),
+    (Action::ToggleRevertEdit, "Edit the message", "--edit"),
+];
+
+/// The modes of `git reset`, only one can be enabled
+const RESET_ARGS: &[PopupArg] = &[
+    (
+        Action::ToggleResetSoft,
+        "Keep the index and work tree",
+        "--soft",
+    ),
+    (
+        Action::ToggleResetMixed,
+        "Reset the index, keep the work tree (default)",
+        "--mixed",
+    ),
+    (
+        Action::ToggleResetHard,
+        "Discard all changes to the index and work tree",
+        "--hard",
+    ),
+    (
+        Action::ToggleResetKeep,
+        "Keep local changes, refuse if they'd be lost",
+        "--keep",
+    ),
+];
+
+const FETCH_ARGS: &[PopupArg] = &[
+    (
+        Action::ToggleFetchPrune,
+        "Prune deleted branches",
+        "--prune",
+    ),
+    (Action::ToggleFetchAll, "Fetch all remotes", "--all"),
+    (Action::ToggleFetchTags, "Fetch all tags", "--tags"),
+];
+
+/// An operation waiting for the user to confirm it
+enum PendingAction {
+    DiscardFile(Section, StatusEntry),
+    /// Reverse applies a patch built from a hunk of the file
+    DiscardPatch(Section, PathBuf, Vec<u8>),
+    /// Deletes a branch that isn't merged
+    ForceDeleteBranch(String),
+    /// Pushes the current branch to a remote and branch, setting it as
+    /// the upstream
+    PushSetUpstream(String, String),
+    DropStash(Stash),
+    AbortOperation(Operation),
+    /// Resets the current branch to a revision with `--hard`
+    HardReset(String),
+}
+
+impl PendingAction {
+    /// The panel it was asked from, which is shown again once it's answered
+    fn panel(&self) -> OpenPanel {
+        match self {
+            PendingAction::ForceDeleteBranch(_) => OpenPanel::Branches,
+            _ => OpenPanel::Staging,
+        }
+    }
+
+    fn prompt(&self) -> String {
+        match self {
+            PendingAction::DiscardFile(Section::Untracked, entry) => {
+                format!("Delete {}?", entry.path.display())
+            }
+            PendingAction::DiscardFile(Section::Staged, entry) => format!(
+                "Discard staged and unstaged changes to {}?",
+                entry.path.display()
+            ),
+            PendingAction::DiscardFile(_, entry) => {
+                format!("Discard unstaged changes to {}?", entry.path.display())
+            }
+            PendingAction::DiscardPatch(Section::Staged, path, _) => format!(
+                "Discard hunk of {} from the index and work tree?",
+                path.display()
+            ),
+            PendingAction::DiscardPatch(_, path, _) => {
+                format!("Discard hunk of {}?", path.display())
+            }
+            PendingAction::ForceDeleteBranch(branch) => {
+                format!("Branch {} is not fully merged, delete it anyway?", branch)
+            }
+            PendingAction::PushSetUpstream(remote, branch) => format!(
+                "The current branch has no upstream, push to {}/{} and set it?",
+                remote, branch
+            ),
+            PendingAction::DropStash(stash) => {
+                format!("Drop {} ({})?", stash.name, stash.message)
+            }
+            PendingAction::AbortOperation(op) => format!(
+                "Abort the {} and lose the changes made since it started?",
+                op.command()
+            ),
+            PendingAction::HardReset(rev) => {
+                format!("Reset to {} and discard all uncommitted changes?", rev)
+            }
+        }
+    }
+}
+
+/// Columns the summary line of a commit message should fit in
+const SUMMARY_WIDTH: usize = 50;
+/// Columns no line of a commit message should go past
+const BODY_WIDTH: usize = 72;
+
+/// How many commits the commit picker lists
+const PICK_COMMITS: usize = 100;
+/// How many more commits the log panel loads at a time
+const LOG_PAGE: usize = 100;
+
+/// The chord bound to `action` in `mode`
+fn chord_for(mode: &dyn Mode, action: Action) -> String {
+    zip(mode.get_bound_chords(), mode.get_bound_actions())
+        .find(|(_, a)| *a == action)
+        .map(|(c, _)| c)
+        .unwrap_or_default()
 }
 
 pub struct Controller {
     running: bool,
-    last_char: char,
+    last_key: Option<Key>,
 
     stage_mode: StageMode,
     commit_mode: StageMode,
     commit_msg_mode: CommitMsgMode,
+    commit_pick_mode: StageMode,
+    confirm_mode: StageMode,
+    pull_mode: StageMode,
+    fetch_mode: StageMode,
+    push_mode: StageMode,
+    branch_mode: StageMode,
+    log_mode: StageMode,
+    revision_mode: StageMode,
+    stash_mode: StageMode,
+    rebase_mode: StageMode,
+    cherry_pick_mode: StageMode,
+    revert_mode: StageMode,
+    reset_mode: StageMode,
+    input_mode: InputMode,
 
     git: Git,
     pub win: Window,
@@ -49,25 +354,127 @@ pub struct Controller {
     pre_commit_layer: Layer,
     commit_msg_layer: Layer,
     help_layer: Layer,
-
-    cursor: Coord,
+    confirm_layer: Layer,
+    pull_layer: Layer,
+    fetch_layer: Layer,
+    push_layer: Layer,
+    input_layer: Layer,
+    branch_layer: Layer,
+    branch_keys_layer: Layer,
+    pick_layer: Layer,
+    pick_keys_layer: Layer,
+    log_layer: Layer,
+    revision_layer: Layer,
+    stash_layer: Layer,
+    rebase_layer: Layer,
+    cherry_pick_layer: Layer,
+    revert_layer: Layer,
+    reset_layer: Layer,
+    job_layer: Layer,
+    notification_layer: Layer,
+
+    /// The status row under the cursor, `None` when there is nothing to
+    /// select
+    cursor: Option<Item>,
+    /// Rows of the status layer the cursor can be on, from top to bottom
+    items: Vec<Item>,
+    /// Where the cursor of the commit message editor is drawn
+    commit_msg_cursor: Coord,
+    /// The first row of the status layer on screen
+    scroll: i32,
+    /// Terminal size the layers were laid out for
+    size: Coord,
 
     fl1_pos: Coord,
     fl2_pos: Coord,
     fl3_pos: Coord,
-
-    fl1_vec: Vec<PathBuf>,
-    fl2_vec: Vec<PathBuf>,
-    fl3_vec: Vec<PathBuf>,
+    unmerged_pos: Coord,
+    stash_pos: Coord,
+
+    status: RepoStatus,
+
+    fl1_vec: Vec<StatusEntry>,
+    fl2_vec: Vec<StatusEntry>,
+    fl3_vec: Vec<StatusEntry>,
+    unmerged_vec: Vec<StatusEntry>,
+    stashes: Vec<Stash>,
+    /// The operation that stopped in the middle, if any
+    operation: Option<Operation>,
+
+    fl1_rows: Vec<ListRow>,
+    fl2_rows: Vec<ListRow>,
+    fl3_rows: Vec<ListRow>,
+    unmerged_rows: Vec<ListRow>,
+
+    /// Files whose diff is shown inline
+    expanded: HashSet<(Section, PathBuf)>,
+    /// Diffs of the expanded files, as last shown
+    diffs: HashMap<(Section, PathBuf), FileDiff>,
+    /// Where a visual line selection was started
+    visual_anchor: Option<(Section, ListRow)>,
+
+    /// Local branches followed by remote ones, as listed in the branch panel
+    branches: Vec<Branch>,
+    branch_cursor: usize,
+    branch_scroll: i32,
+
+    /// Commits listed in the commit picker
+    pick_commits: Vec<Commit>,
+    pick_cursor: usize,
+    pick_scroll: i32,
+    commit_kind: CommitKind,
+
+    /// Lines of the log panel, commits and the graph between them
+    log: Vec<LogLine>,
+    /// The branch the log panel lists, `None` for the current one
+    log_branch: Option<String>,
+    /// The panel the log was opened from and goes back to
+    log_parent: OpenPanel,
+    /// Which of the commits in `log` the cursor is on
+    log_cursor: usize,
+    /// The commit a selection of several was started on
+    log_anchor: Option<usize>,
+    log_scroll: i32,
+    /// Whether the log panel has every commit of the branch
+    log_complete: bool,
+    log_graph: bool,
+    /// The commit opened from the log panel, or the stash opened from the
+    /// status view
+    revision: CommitDetails,
+    revision_scroll: i32,
+    /// The panel the revision was opened from and goes back to
+    revision_parent: OpenPanel,
+
+    /// The todo list of the interactive rebase being prepared
+    rebase_todo: Vec<TodoLine>,
+    rebase_cursor: usize,
+    rebase_scroll: i32,
+    /// The commit the todo list is rebased onto, `None` for the root
+    rebase_base: Option<String>,
+
+    /// The panel the reset popup was opened from and goes back to. Opened
+    /// from the log it resets to the commit under the cursor, otherwise
+    /// to `HEAD~1`.
+    reset_parent: OpenPanel,
 
     open_panel: OpenPanel,
     enabled_commit_args: HashSet<String>,
+    enabled_pull_args: HashSet<String>,
+    enabled_fetch_args: HashSet<String>,
+    enabled_push_args: HashSet<String>,
+    enabled_stash_args: HashSet<String>,
+    enabled_cherry_pick_args: HashSet<String>,
+    enabled_revert_args: HashSet<String>,
+    enabled_reset_args: HashSet<String>,
+    input_purpose: Option<InputPurpose>,
 
     debug_string: String,
 
     log_file: Option<File>,
 
-    push_status: String,
+    notification: Option<Notification>,
+    pending: Option<PendingAction>,
+    job: Option<RunningJob>,
 
     config: Config,
 }
@@ -76,48 +483,183 @@ impl Controller {
     pub fn new(path: PathBuf) -> Controller {
         Controller {
             running: true,
-            last_char: ' ',
+            last_key: None,
             stage_mode: Mode::new(),
             commit_mode: Mode::new(),
             commit_msg_mode: Mode::new(),
+            commit_pick_mode: Mode::new(),
+            confirm_mode: Mode::new(),
+            pull_mode: Mode::new(),
+            fetch_mode: Mode::new(),
+            push_mode: Mode::new(),
+            branch_mode: Mode::new(),
+            log_mode: Mode::new(),
+            revision_mode: Mode::new(),
+            stash_mode: Mode::new(),
+            rebase_mode: Mode::new(),
+            cherry_pick_mode: Mode::new(),
+            revert_mode: Mode::new(),
+            reset_mode: Mode::new(),
+            input_mode: Mode::new(),
             git: Git::new(path),
             win: Window::new(),
             status_layer: Layer::new(),
             pre_commit_layer: Layer::new(),
             commit_msg_layer: Layer::new(),
             help_layer: Layer::new(),
-            cursor: Coord::new(0, 0),
+            confirm_layer: Layer::new(),
+            pull_layer: Layer::new(),
+            fetch_layer: Layer::new(),
+            push_layer: Layer::new(),
+            input_layer: Layer::new(),
+            branch_layer: Layer::new(),
+            branch_keys_layer: Layer::new(),
+            pick_layer: Layer::new(),
+            pick_keys_layer: Layer::new(),
+            log_layer: Layer::new(),
+            revision_layer: Layer::new(),
+            stash_layer: Layer::new(),
+            rebase_layer: Layer::new(),
+            cherry_pick_layer: Layer::new(),
+            revert_layer: Layer::new(),
+            reset_layer: Layer::new(),
+            job_layer: Layer::new(),
+            notification_layer: Layer::new(),
+            cursor: None,
+            items: Vec::new(),
+            commit_msg_cursor: Coord::new(0, 0),
+            scroll: 0,
+            size: Coord::new(0, 0),
             fl1_pos: Coord::new(0, 0),
             fl2_pos: Coord::new(0, 0),
             fl3_pos: Coord::new(0, 0),
+            unmerged_pos: Coord::new(0, 0),
+            stash_pos: Coord::new(0, 0),
+            status: RepoStatus::default(),
             fl1_vec: Vec::new(),
             fl2_vec: Vec::new(),
             fl3_vec: Vec::new(),
-            open_panel: OpenPanel::STAGING,
+            unmerged_vec: Vec::new(),
+            stashes: Vec::new(),
+            operation: None,
+            fl1_rows: Vec::new(),
+            fl2_rows: Vec::new(),
+            fl3_rows: Vec::new(),
+            unmerged_rows: Vec::new(),
+            expanded: HashSet::new(),
+            diffs: HashMap::new(),
+            visual_anchor: None,
+            branches: Vec::new(),
+            branch_cursor: 0,
+            branch_scroll: 0,
+            pick_commits: Vec::new(),
+            pick_cursor: 0,
+            pick_scroll: 0,
+            commit_kind: CommitKind::Create,
+            log: Vec::new(),
+            log_branch: None,
+            log_parent: OpenPanel::Staging,
+            log_cursor: 0,
+            log_anchor: None,
+            log_scroll: 0,
+            log_complete: false,
+            log_graph: true,
+            revision: CommitDetails::default(),
+            revision_scroll: 0,
+            revision_parent: OpenPanel::Log,
+            rebase_todo: Vec::new(),
+            rebase_cursor: 0,
+            rebase_scroll: 0,
+            rebase_base: None,
+            reset_parent: OpenPanel::Staging,
+            open_panel: OpenPanel::Staging,
             enabled_commit_args: HashSet::new(),
+            enabled_pull_args: HashSet::new(),
+            enabled_fetch_args: HashSet::new(),
+            enabled_push_args: HashSet::new(),
+            enabled_stash_args: HashSet::new(),
+            enabled_cherry_pick_args: HashSet::new(),
+            enabled_revert_args: HashSet::new(),
+            enabled_reset_args: HashSet::new(),
+            input_purpose: None,
             debug_string: String::new(),
             log_file: None,
-            push_status: String::from(""),
+            notification: None,
+            pending: None,
+            job: None,
             config: Config::new(),
         }
     }
 
     pub fn init(&mut self) {
-        self.win.init();
-
-        self.update_status_layer();
-        self.update_pre_commit_layer();
-        self.update_commit_msg_layer();
-        self.update_help_layer();
+        let (config, errors) = Config::load();
+        self.config = config;
+        if !errors.is_empty() {
+            let msg = format!("Errors in the config file:\n{}", errors.join("\n"));
+            self.log(&msg);
+            self.notification = Some(Notification::Error(msg));
+        }
 
-        self.cursor.x = 2;
-        self.cursor.y = 2;
+        self.win.init(&self.config.theme);
 
         self.stage_mode
             .set_key_map(self.config.stage_mode_key_map.clone());
 
         self.commit_mode
             .set_key_map(self.config.commit_mode_key_map.clone());
+
+        self.commit_msg_mode
+            .set_key_map(self.config.commit_msg_mode_key_map.clone());
+
+        self.commit_pick_mode
+            .set_key_map(self.config.commit_pick_mode_key_map.clone());
+
+        self.confirm_mode
+            .set_key_map(self.config.confirm_mode_key_map.clone());
+
+        self.pull_mode
+            .set_key_map(self.config.pull_mode_key_map.clone());
+
+        self.fetch_mode
+            .set_key_map(self.config.fetch_mode_key_map.clone());
+
+        self.push_mode
+            .set_key_map(self.config.push_mode_key_map.clone());
+
+        self.branch_mode
+            .set_key_map(self.config.branch_mode_key_map.clone());
+
+        self.log_mode
+            .set_key_map(self.config.log_mode_key_map.clone());
+
+        self.revision_mode
+            .set_key_map(self.config.revision_mode_key_map.clone());
+
+        self.stash_mode
+            .set_key_map(self.config.stash_mode_key_map.clone());
+
+        self.rebase_mode
+            .set_key_map(self.config.rebase_mode_key_map.clone());
+
+        self.cherry_pick_mode
+            .set_key_map(self.config.cherry_pick_mode_key_map.clone());
+
+        self.revert_mode
+            .set_key_map(self.config.revert_mode_key_map.clone());
+
+        self.reset_mode
+            .set_key_map(self.config.reset_mode_key_map.clone());
+
+        self.update_status_layer();
+        self.update_pre_commit_layer();
+        self.update_commit_msg_layer();
+        self.update_pull_layer();
+        self.update_fetch_layer();
+        self.update_push_layer();
+        self.update_stash_layer();
+        self.update_branch_layer();
+        self.update_help_layer();
+        self.update_notification_layer();
     }
 
     pub fn enable_logging(&mut self) {
@@ -126,341 +668,2208 @@ impl Controller {
 
     pub fn render(&self) {
         self.win.win.clear();
-        if self.open_panel == OpenPanel::STAGING {
-            self.status_layer.render(&self.win.win, Coord::new(0, 0));
-        }
-        if self.open_panel == OpenPanel::COMMITING {
-            self.status_layer.render(&self.win.win, Coord::new(0, 0));
-            self.pre_commit_layer.render(
-                &self.win.win,
-                Coord::new(
-                    0,
-                    self.win.get_size().y - self.pre_commit_layer.size().y - 1,
-                ),
-            );
+        if self.open_panel == OpenPanel::Staging {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
         }
-        if self.open_panel == OpenPanel::COMMITMSG {
+        if self.open_panel == OpenPanel::Commiting {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.pre_commit_layer);
+        }
+        if self.open_panel == OpenPanel::CommitMsg {
             self.commit_msg_layer
                 .render(&self.win.win, Coord::new(0, 0));
         }
-        if self.open_panel == OpenPanel::HELP {
-            self.status_layer.render(&self.win.win, Coord::new(0, 0));
-            self.help_layer.render(
-                &self.win.win,
-                Coord::new(0, self.win.get_size().y - self.help_layer.size().y - 1),
+        if self.open_panel == OpenPanel::Help {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.help_layer);
+        }
+        if self.open_panel == OpenPanel::Confirm {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.confirm_layer);
+        }
+        if self.open_panel == OpenPanel::Pulling {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.pull_layer);
+        }
+        if self.open_panel == OpenPanel::Fetching {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.fetch_layer);
+        }
+        if self.open_panel == OpenPanel::Pushing {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.push_layer);
+        }
+        if self.open_panel == OpenPanel::Input {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.input_layer);
+        }
+        if self.open_panel == OpenPanel::Branches {
+            self.render_scrolled(&self.branch_layer, self.branch_scroll, self.branch_height());
+            self.render_popup(&self.branch_keys_layer);
+        }
+        if self.open_panel == OpenPanel::Log {
+            self.render_scrolled(&self.log_layer, self.log_scroll, self.status_height());
+        }
+        if self.open_panel == OpenPanel::CherryPicking {
+            self.render_scrolled(&self.log_layer, self.log_scroll, self.status_height());
+            self.render_popup(&self.cherry_pick_layer);
+        }
+        if self.open_panel == OpenPanel::Reverting {
+            self.render_scrolled(&self.log_layer, self.log_scroll, self.status_height());
+            self.render_popup(&self.revert_layer);
+        }
+        if self.open_panel == OpenPanel::Resetting {
+            if self.reset_parent == OpenPanel::Log {
+                self.render_scrolled(&self.log_layer, self.log_scroll, self.status_height());
+            } else {
+                self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            }
+            self.render_popup(&self.reset_layer);
+        }
+        if self.open_panel == OpenPanel::Revision {
+            self.render_scrolled(
+                &self.revision_layer,
+                self.revision_scroll,
+                self.status_height(),
             );
         }
+        if self.open_panel == OpenPanel::Rebase {
+            self.render_scrolled(&self.rebase_layer, self.rebase_scroll, self.status_height());
+        }
+        if self.open_panel == OpenPanel::Stashing {
+            self.render_scrolled(&self.status_layer, self.scroll, self.status_height());
+            self.render_popup(&self.stash_layer);
+        }
+        if self.open_panel == OpenPanel::CommitPick {
+            self.render_scrolled(&self.pick_layer, self.pick_scroll, self.pick_height());
+            self.render_popup(&self.pick_keys_layer);
+        }
 
-        let mut i = 0;
-        for thing in &self.enabled_commit_args {
-            self.win.win.mvaddstr(20 + i, 20, thing);
-            i += 1;
+        for (i, thing) in self.enabled_commit_args.iter().enumerate() {
+            self.win.win.mvaddstr(20 + i as i32, 20, thing);
         }
 
-        if self.open_panel != OpenPanel::COMMITMSG {
-            let on_cursor = self.win.win.mvinch(self.cursor.y, self.cursor.x);
-            // Mask out all color bits and apply the "selected" colors
-            self.win.win.mvaddch(
-                self.cursor.y,
-                self.cursor.x,
-                on_cursor & (!COLOR_PAIR(0xFF)) | COLOR_PAIR(COLOR_PAIR_SELECTED.into()),
-            );
+        let visual = if self.over_log() {
+            self.log_visual_rows().map(|rows| (rows, self.log_scroll))
+        } else {
+            self.visual_rows().map(|rows| (rows, self.scroll))
+        };
+        if let Some(((start, end), scroll)) = visual {
+            let height = self.status_height();
+            let start = (start - scroll).max(0);
+            let end = (end - scroll).min(height - 1);
+            for y in start..=end {
+                for x in 0..self.win.get_size().x {
+                    restyle_cell(&self.win.win, y, x, |attributes, pair| {
+                        (attributes | A_REVERSE, pair)
+                    });
+                }
+            }
+        }
+
+        {
+            let cursor = match self.open_panel {
+                OpenPanel::CommitMsg => self.commit_msg_cursor,
+                OpenPanel::Branches => Coord::new(
+                    2,
+                    self.branch_row_y(self.branch_cursor) - self.branch_scroll,
+                ),
+                _ if self.over_log() => match self.log_commit_pos(self.log_cursor) {
+                    Some(pos) => pos - Coord::new(0, self.log_scroll),
+                    None => Coord::new(-1, -1),
+                },
+                OpenPanel::Revision => Coord::new(-1, -1),
+                OpenPanel::Rebase => {
+                    Coord::new(2, 2 + self.rebase_cursor as i32 - self.rebase_scroll)
+                }
+                OpenPanel::CommitPick => {
+                    Coord::new(2, 1 + self.pick_cursor as i32 - self.pick_scroll)
+                }
+                _ => match &self.cursor {
+                    Some(item) => Coord::new(2, item.y - self.scroll),
+                    None => Coord::new(-1, -1),
+                },
+            };
+            // Keep the attributes but apply the "selected" colors
+            restyle_cell(&self.win.win, cursor.y, cursor.x, |attributes, _| {
+                (attributes, COLOR_PAIR_SELECTED)
+            });
+        }
+        if let Some(key) = self.last_key {
+            self.win.win.mvaddstr(15, 0, key.to_string());
         }
-        self.win.win.mvaddch(15, 0, self.last_char);
         self.win
             .win
-            .mvaddstr(16, 0, &format!("{:?}", self.open_panel));
+            .mvaddstr(16, 0, format!("{:?}", self.open_panel));
         self.win
             .win
-            .mvaddstr(19, 0, &format!("Debug msg: {:?}", self.debug_string));
+            .mvaddstr(19, 0, format!("Debug msg: {:?}", self.debug_string));
 
-        if self.push_status != String::from("") {
-            let pos = Coord::new(0, self.fl3_pos.y + self.fl3_vec.len() as i32 + 1);
-            let mut push_msg: Text = UiElement::new();
-            push_msg.content = self.push_status.clone();
-            push_msg.style = TextStyle::BOLD;
-            push_msg.c_pair = COLOR_PAIR_H1;
-            push_msg.render(&self.win.win, pos);
-        }
+        self.notification_layer.render(
+            &self.win.win,
+            Coord::new(0, self.win.get_size().y - self.notification_layer.size().y),
+        );
+        // Popups leave a line free above the notification for this
+        self.job_layer.render(
+            &self.win.win,
+            Coord::new(
+                0,
+                self.win.get_size().y - self.notification_layer.size().y - 1,
+            ),
+        );
 
         self.win.render();
     }
 
+    /// Whether the log panel is shown, on its own or under a popup opened
+    /// from it
+    fn over_log(&self) -> bool {
+        match self.open_panel {
+            OpenPanel::Log | OpenPanel::CherryPicking | OpenPanel::Reverting => true,
+            OpenPanel::Resetting => self.reset_parent == OpenPanel::Log,
+            _ => false,
+        }
+    }
+
+    /// Renders the rows of `layer` from `scroll` on into the top `height`
+    /// rows of the screen, anything outside of those is cut off.
+    fn render_scrolled(&self, layer: &Layer, scroll: i32, height: i32) {
+        let size = self.win.get_size();
+        // A height of 0 would make the view as large as the screen
+        if let Ok(view) = self.win.win.derwin(height.clamp(1, size.y), size.x, 0, 0) {
+            layer.render(&view, Coord::new(0, -scroll));
+        }
+    }
+
+    /// Rows of the screen the status is shown in, the rest is taken by the
+    /// notification and the job
+    fn status_height(&self) -> i32 {
+        self.win.get_size().y - self.notification_layer.size().y - self.job.is_some() as i32
+    }
+
+    /// Rows of the screen the branch list is shown in, above its popup
+    fn branch_height(&self) -> i32 {
+        self.win.get_size().y
+            - self.notification_layer.size().y
+            - 1
+            - self.branch_keys_layer.size().y
+    }
+
+    /// Rows of the screen the commit picker is shown in, above its popup
+    fn pick_height(&self) -> i32 {
+        self.win.get_size().y - self.notification_layer.size().y - 1 - self.pick_keys_layer.size().y
+    }
+
+    /// Keeps the cursors on the rows of their layers and scrolls the layers
+    /// so the cursors are on screen.
+    fn scroll_to_cursor(&mut self) {
+        let row = self.cursor.as_ref().map_or(0, |item| item.y);
+        self.scroll = follow(
+            self.scroll,
+            row,
+            self.status_height(),
+            self.status_layer.size().y,
+        );
+        self.branch_scroll = follow(
+            self.branch_scroll,
+            self.branch_row_y(self.branch_cursor),
+            self.branch_height(),
+            self.branch_layer.size().y,
+        );
+        self.pick_scroll = follow(
+            self.pick_scroll,
+            1 + self.pick_cursor as i32,
+            self.pick_height(),
+            self.pick_layer.size().y,
+        );
+        // The header comes into view together with the first commit
+        let log_row = match self.log_commit_pos(self.log_cursor) {
+            Some(pos) if self.log_cursor > 0 => pos.y,
+            _ => 0,
+        };
+        self.log_scroll = follow(
+            self.log_scroll,
+            log_row,
+            self.status_height(),
+            self.log_layer.size().y,
+        );
+        let rebase_row = match self.rebase_cursor {
+            0 => 0,
+            i => 2 + i as i32,
+        };
+        self.rebase_scroll = follow(
+            self.rebase_scroll,
+            rebase_row,
+            self.status_height(),
+            self.rebase_layer.size().y,
+        );
+        // The revision has no cursor, it's scrolled directly
+        let revision_rows = self.revision_layer.size().y;
+        self.revision_scroll = self
+            .revision_scroll
+            .clamp(0, (revision_rows - self.status_height()).max(0));
+    }
+
+    /// Lays everything out again if the terminal changed size since the
+    /// last call.
+    pub fn fit_to_screen(&mut self) {
+        let size = self.win.get_size();
+        if size != self.size {
+            self.size = size;
+            self.update_layers();
+        }
+    }
+
+    /// Renders `layer` at the bottom of the screen, above any notification
+    fn render_popup(&self, layer: &Layer) {
+        let top = self.win.get_size().y - layer.size().y - self.notification_layer.size().y - 1;
+        // Whatever the popup is drawn over mustn't show through its gaps
+        for y in top..top + layer.size().y {
+            self.win.win.mv(y, 0);
+            self.win.win.clrtoeol();
+        }
+        layer.render(&self.win.win, Coord::new(0, top));
+    }
+
     pub fn running(&self) -> bool {
         self.running
     }
 
     pub fn close(&mut self) {
+        if let Some(running) = self.job.take() {
+            running.job.cancel();
+        }
         self.running = false;
         self.win.close();
     }
 
-    pub fn handle_key(&mut self, key: i32) {
-        self.last_char = key as u8 as char;
-        self.push_status = String::from("");
+    pub fn handle_key(&mut self, key: Key) {
+        self.last_key = Some(key);
+        self.notification = None;
 
         self.debug_string.clear();
 
         match self.open_panel {
-            OpenPanel::STAGING => match self.stage_mode.handle_key(key) {
+            OpenPanel::Staging => match self.stage_mode.handle_key(key) {
                 Action::CursorDown => self.cursor_move(1),
                 Action::CursorUp => self.cursor_move(-1),
+                Action::CursorBufferStart => self.cursor_to(0),
+                Action::CursorBufferEnd => self.cursor_to(self.items.len().saturating_sub(1)),
+                Action::NextSection => self.jump_section(true),
+                Action::PrevSection => self.jump_section(false),
+                Action::Exit if self.visual_anchor.is_some() => self.visual_anchor = None,
                 Action::Exit => self.close(),
-                Action::StageFile => {
-                    let file = self.get_file();
-                    match file {
-                        Some(p) => self.git.stage_file(p),
-                        None => (),
+                Action::StageFile => self.stage_selection(false),
+                Action::UnstageFile => self.stage_selection(true),
+                Action::Discard if self.on_stash() => self.drop_stash(),
+                Action::Discard => self.discard_selection(),
+                Action::ToggleExpand if self.on_stash() => self.show_stash(),
+                Action::TakeOurs => self.take_side(Side::Ours),
+                Action::TakeTheirs => self.take_side(Side::Theirs),
+                Action::Mergetool => self.mergetool(),
+                Action::ContinueOperation => match self.operation {
+                    Some(op) => {
+                        let result = self.git.continue_operation(op);
+                        self.report(result);
+                    }
+                    None => self.nothing_in_progress(),
+                },
+                Action::SkipOperation => match self.operation {
+                    Some(op) if op.can_skip() => {
+                        let result = self.git.skip_operation(op);
+                        self.report(result);
+                    }
+                    Some(op) => {
+                        self.notification = Some(Notification::Error(format!(
+                            "A {} can't be skipped",
+                            op.command()
+                        )))
+                    }
+                    None => self.nothing_in_progress(),
+                },
+                Action::AbortOperation => match self.operation {
+                    Some(op) => {
+                        self.pending = Some(PendingAction::AbortOperation(op));
+                        self.open_panel = OpenPanel::Confirm;
                     }
+                    None => self.nothing_in_progress(),
+                },
+                Action::ToggleExpand => self.toggle_expand(),
+                Action::ToggleVisualSelect => {
+                    self.visual_anchor = match (self.visual_anchor, self.get_row()) {
+                        (None, Some((section, row @ ListRow::Line(..)))) => Some((section, row)),
+                        _ => None,
+                    }
+                }
+                Action::OpenCommitMode => self.open_panel = OpenPanel::Commiting,
+                Action::OpenPullMode => self.open_panel = OpenPanel::Pulling,
+                Action::OpenFetchMode => self.open_panel = OpenPanel::Fetching,
+                Action::OpenBranchMode => {
+                    self.load_branches();
+                    self.branch_cursor = 0;
+                    self.open_panel = OpenPanel::Branches;
                 }
-                Action::UnstageFile => {
-                    let file = self.get_file();
-                    match file {
-                        Some(p) => self.git.unstage_file(p),
-                        None => (),
+                Action::OpenPushMode => self.open_panel = OpenPanel::Pushing,
+                Action::OpenStashMode => self.open_panel = OpenPanel::Stashing,
+                Action::OpenResetMode => self.open_reset(),
+                Action::OpenLogMode => self.open_log(None),
+                Action::CancelJob => self.cancel_job(),
+                Action::OpenHelpMode => self.open_panel = OpenPanel::Help,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Commiting => match self.commit_mode.handle_key(key) {
+                Action::OpenCommitMsgMode => self.open_commit_msg(CommitKind::Create),
+                Action::CommitAmend => self.open_commit_msg(CommitKind::Amend),
+                Action::CommitReword => self.open_commit_msg(CommitKind::Reword),
+                Action::CommitExtend => {
+                    let args = enabled_args(COMMIT_ARGS, &self.enabled_commit_args);
+                    let result = self.git.extend(args);
+                    self.report(result);
+                    self.open_panel = OpenPanel::Staging;
+                }
+                Action::CommitFixup => self.open_commit_pick(CommitKind::Fixup),
+                Action::CommitSquash => self.open_commit_pick(CommitKind::Squash),
+                a if toggle_arg(&mut self.enabled_commit_args, COMMIT_ARGS, a) => {}
+                Action::CommitInEditor => self.commit_in_editor(),
+                Action::Exit => self.open_panel = OpenPanel::Staging,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::CommitMsg => match self.commit_msg_mode.handle_key(key) {
+                Action::Exit => self.open_panel = OpenPanel::Commiting,
+                Action::ConfirmCommitMsg => {
+                    let args = enabled_args(COMMIT_ARGS, &self.enabled_commit_args);
+                    let msg = self.commit_msg_mode.buffer.text();
+                    let result = match self.commit_kind {
+                        CommitKind::Amend => self.git.amend(args, msg),
+                        CommitKind::Reword => self.git.reword(args, msg),
+                        _ => self.git.commit(args, msg),
+                    };
+                    if self.report(result).is_some() {
+                        self.commit_msg_mode.buffer.clear();
+                        self.commit_kind = CommitKind::Create;
                     }
+                    self.open_panel = OpenPanel::Staging;
                 }
-                Action::OpenCommitMode => self.open_panel = OpenPanel::COMMITING,
-                Action::Push => {
-                    self.debug_string = String::from("Push complete");
-                    self.render_push_start();
-                    let result = self.git.push();
-                    match self.log_file {
-                        Some(ref mut file) => match file.write(result.as_bytes()) {
-                            Err(_) => println!("Error while writing to debug file"),
-                            _ => {}
-                        },
-                        None => {}
+                _ => {}
+            },
+            OpenPanel::Confirm => match self.confirm_mode.handle_key(key) {
+                Action::Confirm => {
+                    self.open_panel = OpenPanel::Staging;
+                    if let Some(pending) = self.pending.take() {
+                        self.open_panel = pending.panel();
+                        self.run_pending(pending);
                     }
-                    self.push_status = result;
                 }
-                Action::OpenHelpMode => self.open_panel = OpenPanel::HELP,
+                Action::Exit => {
+                    self.open_panel = self
+                        .pending
+                        .take()
+                        .map_or(OpenPanel::Staging, |p| p.panel());
+                }
+                // Anything else is ignored, a stray key shouldn't count as
+                // an answer
+                _ => {}
+            },
+            OpenPanel::Pulling => match self.pull_mode.handle_key(key) {
+                Action::PullUpstream => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.pull(&[]);
+                }
+                Action::PullElsewhere => self.open_input(InputPurpose::PullFrom),
+                a if toggle_arg(&mut self.enabled_pull_args, PULL_ARGS, a) => {}
+                Action::Exit => self.open_panel = OpenPanel::Staging,
                 a => self.debug_string = format!("Unbound action {:?}", a),
             },
-            OpenPanel::COMMITING => match self.commit_mode.handle_key(key) {
-                Action::OpenCommitMsgMode => {
-                    self.open_panel = OpenPanel::COMMITMSG;
-                    self.update_commit_msg_layer();
-                }
-                Action::ToggleCommitAllowEmpty => {
-                    if !self.enabled_commit_args.insert("-e".to_string()) {
-                        self.enabled_commit_args.remove("-e");
-                    }
+            OpenPanel::Pushing => match self.push_mode.handle_key(key) {
+                Action::Push => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.push(enabled_args(PUSH_ARGS, &self.enabled_push_args), &[]);
+                }
+                Action::PushElsewhere => self.open_input(InputPurpose::PushTo),
+                a if toggle_arg(&mut self.enabled_push_args, PUSH_ARGS, a) => {}
+                Action::Exit => self.open_panel = OpenPanel::Staging,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Stashing => match self.stash_mode.handle_key(key) {
+                Action::Stash => self.open_input(InputPurpose::StashMessage),
+                Action::StashApply => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.apply_stash(false);
+                }
+                Action::StashPop => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.apply_stash(true);
+                }
+                Action::StashDrop => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.drop_stash();
                 }
-                Action::ToggleCommitDisableHooks => {
-                    if !self.enabled_commit_args.insert("-n".to_string()) {
-                        self.enabled_commit_args.remove("-n");
+                Action::StashShow => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.show_stash();
+                }
+                a if toggle_arg(&mut self.enabled_stash_args, STASH_ARGS, a) => {}
+                Action::Exit => self.open_panel = OpenPanel::Staging,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Fetching => match self.fetch_mode.handle_key(key) {
+                Action::FetchUpstream => {
+                    self.open_panel = OpenPanel::Staging;
+                    self.fetch(None);
+                }
+                Action::FetchElsewhere => self.open_input(InputPurpose::FetchFrom),
+                a if toggle_arg(&mut self.enabled_fetch_args, FETCH_ARGS, a) => {}
+                Action::Exit => self.open_panel = OpenPanel::Staging,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Input => match self.input_mode.handle_key(key) {
+                Action::Confirm => {
+                    self.open_panel = OpenPanel::Staging;
+                    let input = std::mem::take(&mut self.input_mode.input);
+                    let words: Vec<&str> = input.split_whitespace().collect();
+                    match (self.input_purpose.take(), words.first()) {
+                        // An empty message leaves it up to git
+                        (Some(InputPurpose::StashMessage), _) => self.stash(input.trim()),
+                        (Some(InputPurpose::Mainline(op)), parent) => {
+                            self.enable_mainline(op, parent.copied())
+                        }
+                        (
+                            Some(InputPurpose::CreateBranch(_) | InputPurpose::RenameBranch(_)),
+                            None,
+                        ) => self.open_panel = OpenPanel::Branches,
+                        (_, None) => {}
+                        (Some(InputPurpose::ResetTo), Some(rev)) => self.reset(rev),
+                        (Some(InputPurpose::PullFrom), _) => self.pull(&words),
+                        (Some(InputPurpose::FetchFrom), remote) => self.fetch(remote.copied()),
+                        (Some(InputPurpose::PushTo), _) => {
+                            self.push(enabled_args(PUSH_ARGS, &self.enabled_push_args), &words)
+                        }
+                        (Some(InputPurpose::CreateBranch(start)), Some(name)) => {
+                            let result = self.git.create_branch(name, &start);
+                            self.report(result);
+                        }
+                        (Some(InputPurpose::RenameBranch(old)), Some(new)) => {
+                            let result = self.git.rename_branch(&old, new);
+                            self.report(result);
+                            self.load_branches();
+                            self.open_panel = OpenPanel::Branches;
+                        }
+                        (None, _) => {}
                     }
                 }
-                Action::ToggleCommitResetAuthor => {
-                    if !self.enabled_commit_args.insert("-R".to_string()) {
-                        self.enabled_commit_args.remove("-R");
+                Action::Exit => {
+                    self.input_mode.input.clear();
+                    self.open_panel = match self.input_purpose.take() {
+                        Some(InputPurpose::CreateBranch(_) | InputPurpose::RenameBranch(_)) => {
+                            OpenPanel::Branches
+                        }
+                        Some(InputPurpose::Mainline(Operation::Revert)) => OpenPanel::Reverting,
+                        Some(InputPurpose::Mainline(_)) => OpenPanel::CherryPicking,
+                        _ => OpenPanel::Staging,
+                    };
+                }
+                _ => {}
+            },
+            OpenPanel::Branches => match self.branch_mode.handle_key(key) {
+                Action::CursorDown => {
+                    self.branch_cursor =
+                        (self.branch_cursor + 1).min(self.branches.len().saturating_sub(1));
+                }
+                Action::CursorUp => self.branch_cursor = self.branch_cursor.saturating_sub(1),
+                Action::CursorBufferStart => self.branch_cursor = 0,
+                Action::CursorBufferEnd => {
+                    self.branch_cursor = self.branches.len().saturating_sub(1)
+                }
+                Action::CheckoutBranch => self.checkout_selected_branch(),
+                Action::CreateBranch => {
+                    if let Some(branch) = self.branches.get(self.branch_cursor) {
+                        let start = branch.name.clone();
+                        self.open_input(InputPurpose::CreateBranch(start));
                     }
                 }
-                Action::ToggleCommitStageAll => {
-                    if !self.enabled_commit_args.insert("-a".to_string()) {
-                        self.enabled_commit_args.remove("-a");
+                Action::RenameBranch => match self.branches.get(self.branch_cursor) {
+                    Some(branch) if branch.remote => {
+                        self.notification = Some(Notification::Error(String::from(
+                            "Only local branches can be renamed",
+                        )));
+                    }
+                    Some(branch) => {
+                        let old = branch.name.clone();
+                        self.open_input(InputPurpose::RenameBranch(old));
+                    }
+                    None => {}
+                },
+                Action::DeleteBranch => self.delete_selected_branch(),
+                Action::OpenLogMode => {
+                    if let Some(branch) = self.branches.get(self.branch_cursor) {
+                        let name = branch.name.clone();
+                        self.open_log(Some(name));
                     }
                 }
-                Action::ToggleCommitVerbose => {
-                    if !self.enabled_commit_args.insert("-v".to_string()) {
-                        self.enabled_commit_args.remove("-v");
+                Action::Exit => self.open_panel = OpenPanel::Staging,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::CommitPick => match self.commit_pick_mode.handle_key(key) {
+                Action::CursorDown => {
+                    self.pick_cursor =
+                        (self.pick_cursor + 1).min(self.pick_commits.len().saturating_sub(1));
+                }
+                Action::CursorUp => self.pick_cursor = self.pick_cursor.saturating_sub(1),
+                Action::CursorBufferStart => self.pick_cursor = 0,
+                Action::CursorBufferEnd => {
+                    self.pick_cursor = self.pick_commits.len().saturating_sub(1)
+                }
+                Action::Confirm => self.commit_for_picked(),
+                Action::Exit => self.open_panel = OpenPanel::Commiting,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Log => match self.log_mode.handle_key(key) {
+                Action::CursorDown => self.log_cursor_to(self.log_cursor + 1),
+                Action::CursorUp => self.log_cursor_to(self.log_cursor.saturating_sub(1)),
+                Action::CursorBufferStart => self.log_cursor_to(0),
+                Action::CursorBufferEnd => self.log_cursor_to(usize::MAX),
+                Action::ShowCommit => self.show_selected_commit(),
+                Action::RebaseInteractive => self.open_rebase(),
+                Action::ToggleVisualSelect => {
+                    self.log_anchor = match self.log_anchor {
+                        None => Some(self.log_cursor),
+                        Some(_) => None,
                     }
                 }
-                Action::Exit => self.open_panel = OpenPanel::STAGING,
+                Action::OpenCherryPickMode => self.open_panel = OpenPanel::CherryPicking,
+                Action::OpenRevertMode => self.open_panel = OpenPanel::Reverting,
+                Action::OpenResetMode => self.open_reset(),
+                Action::ToggleLogGraph => {
+                    // The graph orders the commits differently, stay on
+                    // the same one
+                    let oid = self.selected_commit().map(|c| c.oid.clone());
+                    self.log_graph = !self.log_graph;
+                    self.load_log();
+                    let position =
+                        |c: &Self| c.log_commits().position(|c| Some(&c.oid) == oid.as_ref());
+                    while position(self).is_none() && self.load_log_page(Some(LOG_PAGE)) {}
+                    self.log_cursor = position(self).unwrap_or(0);
+                    self.log_anchor = None;
+                }
+                Action::Exit if self.log_anchor.is_some() => self.log_anchor = None,
+                Action::Exit => self.open_panel = self.log_parent,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::CherryPicking => match self.cherry_pick_mode.handle_key(key) {
+                Action::CherryPick => self.sequence_selected(Operation::CherryPick),
+                Action::ToggleCherryPickMainline
+                    if enabled_flag(&self.enabled_cherry_pick_args, "--mainline=").is_none() =>
+                {
+                    self.open_input(InputPurpose::Mainline(Operation::CherryPick))
+                }
+                a if toggle_arg(&mut self.enabled_cherry_pick_args, CHERRY_PICK_ARGS, a) => {}
+                Action::Exit => self.open_panel = OpenPanel::Log,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Reverting => match self.revert_mode.handle_key(key) {
+                Action::Revert => self.sequence_selected(Operation::Revert),
+                Action::ToggleRevertMainline
+                    if enabled_flag(&self.enabled_revert_args, "--mainline=").is_none() =>
+                {
+                    self.open_input(InputPurpose::Mainline(Operation::Revert))
+                }
+                a if toggle_arg(&mut self.enabled_revert_args, REVERT_ARGS, a) => {}
+                Action::Exit => self.open_panel = OpenPanel::Log,
                 a => self.debug_string = format!("Unbound action {:?}", a),
             },
-            OpenPanel::COMMITMSG => {
-                match self.commit_msg_mode.handle_key(key) {
-                    Action::Exit => self.open_panel = OpenPanel::COMMITING,
-                    Action::ConfirmCommitMsg => {
-                        self.git.commit(
-                            self.enabled_commit_args.clone().into_iter().collect(),
-                            self.commit_msg_mode.commit_msg.clone(),
-                        );
-                        self.open_panel = OpenPanel::STAGING;
+            OpenPanel::Resetting => match self.reset_mode.handle_key(key) {
+                Action::Reset => match self.reset_target() {
+                    Some(rev) => self.reset(&rev),
+                    None => {
+                        self.notification = Some(Notification::Error(String::from(
+                            "There is no commit to reset to",
+                        )));
+                        self.open_panel = self.reset_parent;
                     }
-                    // TODO: Handle åäö, they fuck everything up
-                    Action::WriteChar => self.update_commit_msg_layer(),
-                    _ => {}
+                },
+                Action::ResetElsewhere => self.open_input(InputPurpose::ResetTo),
+                a if toggle_exclusive_arg(&mut self.enabled_reset_args, RESET_ARGS, a) => {}
+                Action::Exit => self.open_panel = self.reset_parent,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Rebase => match self.rebase_mode.handle_key(key) {
+                Action::CursorDown => {
+                    self.rebase_cursor =
+                        (self.rebase_cursor + 1).min(self.rebase_todo.len().saturating_sub(1));
                 }
-            }
-            _ => self.open_panel = OpenPanel::STAGING,
+                Action::CursorUp => self.rebase_cursor = self.rebase_cursor.saturating_sub(1),
+                Action::CursorBufferStart => self.rebase_cursor = 0,
+                Action::CursorBufferEnd => {
+                    self.rebase_cursor = self.rebase_todo.len().saturating_sub(1)
+                }
+                Action::RebasePick => self.set_todo_verb(TodoVerb::Pick),
+                Action::RebaseReword => self.set_todo_verb(TodoVerb::Reword),
+                Action::RebaseEdit => self.set_todo_verb(TodoVerb::Edit),
+                Action::RebaseSquash => self.set_todo_verb(TodoVerb::Squash),
+                Action::RebaseFixup => self.set_todo_verb(TodoVerb::Fixup),
+                Action::RebaseDrop => self.set_todo_verb(TodoVerb::Drop),
+                Action::RebaseMoveDown => self.move_todo_line(true),
+                Action::RebaseMoveUp => self.move_todo_line(false),
+                Action::Confirm => self.start_rebase(),
+                Action::Exit => self.open_panel = OpenPanel::Log,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            OpenPanel::Revision => match self.revision_mode.handle_key(key) {
+                Action::CursorDown => self.revision_scroll += 1,
+                Action::CursorUp => self.revision_scroll -= 1,
+                Action::CursorBufferStart => self.revision_scroll = 0,
+                Action::CursorBufferEnd => self.revision_scroll = i32::MAX,
+                Action::Exit => self.open_panel = self.revision_parent,
+                a => self.debug_string = format!("Unbound action {:?}", a),
+            },
+            _ => self.open_panel = OpenPanel::Staging,
             /*
-            OpenPanel::HELP => {
+            OpenPanel::Help => {
                 if self.key_chord == vec![27] {
-                    self.open_panel = OpenPanel::STAGING;
+                    self.open_panel = OpenPanel::Staging;
                 }
             }
             */
         }
         self.debug_string = format!("{:?}", key);
+        self.update_layers();
+    }
+
+    fn update_layers(&mut self) {
         self.update_status_layer();
         self.update_pre_commit_layer();
+        self.update_commit_msg_layer();
+        self.update_pull_layer();
+        self.update_fetch_layer();
+        self.update_push_layer();
+        self.update_stash_layer();
+        self.update_cherry_pick_layer();
+        self.update_revert_layer();
+        self.update_reset_layer();
+        self.update_branch_layer();
+        self.update_pick_layer();
+        self.update_log_layer();
+        self.update_rebase_layer();
+        self.update_revision_layer();
         self.update_help_layer();
+        self.update_confirm_layer();
+        self.update_input_layer();
+        self.update_job_layer();
+        self.update_notification_layer();
+        self.scroll_to_cursor();
+    }
+
+    /// Handles whatever the background job reported since the last call.
+    /// Until it is done the window stops waiting for keys every
+    /// `JOB_POLL_MS` so this gets called regularly.
+    pub fn poll_job(&mut self) {
+        let mut done = None;
+        if let Some(running) = &mut self.job {
+            while let Some(event) = running.job.poll() {
+                match event {
+                    JobEvent::Line(line) => {
+                        running.last_line = line;
+                        running.progress = None;
+                    }
+                    JobEvent::Progress(progress) => running.progress = Some(progress),
+                    JobEvent::Done(result) => {
+                        done = Some(result);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(result) = done {
+            if let Some(running) = self.job.take() {
+                self.finish_job(running.kind, result);
+            }
+            self.update_layers();
+        }
+        self.update_job_layer();
+
+        self.win
+            .set_timeout(if self.job.is_some() { JOB_POLL_MS } else { -1 });
+    }
+
+    fn finish_job(&mut self, kind: JobKind, result: GitResult<Output>) {
+        if let Err(err) = &result {
+            if err.is_auth_failure() {
+                let msg = format!(
+                    "Can't prompt for credentials, set up a credential helper or an ssh agent\n{}",
+                    err
+                );
+                self.log(&msg);
+                self.notification = Some(Notification::Error(msg));
+                return;
+            }
+        }
+        if kind == JobKind::Push {
+            if let Some((remote, branch)) =
+                result.as_ref().err().and_then(|e| e.suggested_upstream())
+            {
+                self.pending = Some(PendingAction::PushSetUpstream(remote, branch));
+                self.open_panel = OpenPanel::Confirm;
+                return;
+            }
+        }
+
+        let output = match self.report(result) {
+            Some(output) => output,
+            None => return,
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let msg = match kind {
+            JobKind::Fetch => FetchResult::parse(&stderr).to_string(),
+            JobKind::Pull => PullResult::parse(&stdout, &stderr).to_string(),
+            JobKind::Push => stderr.trim_end().to_string(),
+        };
+        self.log(&msg);
+        self.notification = Some(Notification::Info(msg));
+    }
+
+    /// Runs `job` in the background
+    fn start_job(&mut self, kind: JobKind, job: GitResult<Job>) {
+        if let Some(job) = self.report(job) {
+            self.job = Some(RunningJob {
+                kind,
+                job,
+                progress: None,
+                last_line: String::new(),
+            });
+        }
+    }
+
+    /// Whether a job is running, in which case no other one can be started
+    fn job_running(&mut self) -> bool {
+        let command = match &self.job {
+            Some(running) => running.job.command().to_string(),
+            None => return false,
+        };
+        self.notification = Some(Notification::Error(format!(
+            "`{}` is still running",
+            command
+        )));
+        true
+    }
+
+    fn cancel_job(&mut self) {
+        if let Some(running) = self.job.take() {
+            running.job.cancel();
+            self.notification = Some(Notification::Info(format!(
+                "Cancelled `{}`",
+                running.job.command()
+            )));
+        }
+    }
+
+    fn log(&mut self, msg: &str) {
+        if let Some(ref mut file) = self.log_file {
+            if writeln!(file, "{}", msg).is_err() {
+                println!("Error while writing to debug file");
+            }
+        }
+    }
+
+    /// Unwraps the result of a git operation, showing the error in the
+    /// notification area if it failed.
+    fn report<T>(&mut self, result: GitResult<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                let msg = err.to_string();
+                self.log(&msg);
+                self.notification = Some(Notification::Error(msg));
+                None
+            }
+        }
+    }
+
+    fn pull(&mut self, from: &[&str]) {
+        if self.job_running() {
+            return;
+        }
+        let args = enabled_args(PULL_ARGS, &self.enabled_pull_args);
+        let job = self.git.pull(args, from);
+        self.start_job(JobKind::Pull, job);
+    }
+
+    /// Pushes with `args`. If the current branch turns out to have no
+    /// upstream, asks whether to push to where git suggests and set it.
+    fn push(&mut self, args: Vec<String>, to: &[&str]) {
+        if self.job_running() {
+            return;
+        }
+        let job = self.git.push(args, to);
+        self.start_job(JobKind::Push, job);
+    }
+
+    fn fetch(&mut self, remote: Option<&str>) {
+        if self.job_running() {
+            return;
+        }
+        let args = enabled_args(FETCH_ARGS, &self.enabled_fetch_args);
+        let job = self.git.fetch(args, remote);
+        self.start_job(JobKind::Fetch, job);
+    }
+
+    fn load_branches(&mut self) {
+        self.branches = self.report(self.git.branches()).unwrap_or_default();
+        self.branches.sort_by_key(|b| b.remote);
+        self.branch_cursor = self
+            .branch_cursor
+            .min(self.branches.len().saturating_sub(1));
+    }
+
+    /// Screen row of branch `i` in the branch panel, remote branches are
+    /// listed below a header of their own.
+    fn branch_row_y(&self, i: usize) -> i32 {
+        let locals = self.branches.iter().filter(|b| !b.remote).count();
+        if i < locals {
+            1 + i as i32
+        } else {
+            3 + i as i32
+        }
+    }
+
+    /// Checks out the branch under the cursor. For remote branches the
+    /// local branch of the same name is used, created if it doesn't exist.
+    fn checkout_selected_branch(&mut self) {
+        let branch = match self.branches.get(self.branch_cursor) {
+            Some(b) => b.clone(),
+            None => return,
+        };
+
+        let local = branch.local_name();
+        let result = if !branch.remote {
+            self.git.checkout(&branch.name)
+        } else if self.branches.iter().any(|b| !b.remote && b.name == local) {
+            self.git.checkout(local)
+        } else {
+            self.git.checkout_tracking(&branch.name)
+        };
+
+        if self.report(result).is_some() {
+            self.open_panel = OpenPanel::Staging;
+        }
+    }
+
+    /// Deletes the local branch under the cursor, asking before deleting it
+    /// if it isn't merged.
+    fn delete_selected_branch(&mut self) {
+        let name = match self.branches.get(self.branch_cursor) {
+            Some(b) if b.remote => {
+                self.notification = Some(Notification::Error(String::from(
+                    "Only local branches can be deleted",
+                )));
+                return;
+            }
+            Some(b) => b.name.clone(),
+            None => return,
+        };
+
+        match self.git.delete_branch(&name, false) {
+            Err(err) if err.is_unmerged_branch() => {
+                self.pending = Some(PendingAction::ForceDeleteBranch(name));
+                self.open_panel = OpenPanel::Confirm;
+            }
+            result => {
+                self.report(result);
+                self.load_branches();
+            }
+        }
+    }
+
+    /// Opens the message editor for a `kind` commit. Amending and rewording
+    /// start from the message of HEAD, which isn't kept as a draft of the
+    /// next new commit.
+    fn open_commit_msg(&mut self, kind: CommitKind) {
+        if kind != CommitKind::Create {
+            let msg = match self.report(self.git.head_message()) {
+                Some(msg) => msg,
+                None => return,
+            };
+            self.commit_msg_mode.buffer.set_text(&msg);
+        } else if self.commit_kind != CommitKind::Create {
+            self.commit_msg_mode.buffer.clear();
+        }
+        self.commit_kind = kind;
+        self.open_panel = OpenPanel::CommitMsg;
+        self.update_commit_msg_layer();
+    }
+
+    /// Lists the commits of the current branch to pick the one a `kind`
+    /// commit is made for
+    fn open_commit_pick(&mut self, kind: CommitKind) {
+        let lines = match self.report(self.git.log(Some(PICK_COMMITS), 0, false, None)) {
+            Some(lines) => lines,
+            None => return,
+        };
+        self.pick_commits = lines.into_iter().filter_map(|l| l.commit).collect();
+        self.pick_cursor = 0;
+        self.commit_kind = kind;
+        self.open_panel = OpenPanel::CommitPick;
+    }
+
+    /// Makes the fixup or squash commit for the commit under the cursor of
+    /// the picker
+    fn commit_for_picked(&mut self) {
+        let oid = match self.pick_commits.get(self.pick_cursor) {
+            Some(commit) => commit.oid.clone(),
+            None => return,
+        };
+        let args = enabled_args(COMMIT_ARGS, &self.enabled_commit_args);
+        let result = match self.commit_kind {
+            CommitKind::Squash => self.git.squash(args, &oid),
+            _ => self.git.fixup(args, &oid),
+        };
+        self.report(result);
+        self.open_panel = OpenPanel::Staging;
+    }
+
+    /// Opens the log panel on `branch`, or the current branch if `None`
+    fn open_log(&mut self, branch: Option<String>) {
+        self.log_branch = branch;
+        self.log_cursor = 0;
+        self.log_anchor = None;
+        if self.load_log() {
+            self.log_parent = self.open_panel;
+            self.open_panel = OpenPanel::Log;
+        }
+    }
+
+    /// Loads the first page of commits into the log panel. Returns false
+    /// if git couldn't list them.
+    fn load_log(&mut self) -> bool {
+        self.log.clear();
+        self.log_complete = false;
+        self.load_log_page(Some(LOG_PAGE))
+    }
+
+    /// Adds up to `count` more commits to the log panel, every one that's
+    /// left without a `count`. Returns false if there were none to add or
+    /// git couldn't list them.
+    fn load_log_page(&mut self, count: Option<usize>) -> bool {
+        if self.log_complete {
+            return false;
+        }
+        let loaded = self.log_commits().count();
+        let result = self
+            .git
+            .log(count, loaded, self.log_graph, self.log_branch.as_deref());
+        let lines = match self.report(result) {
+            Some(lines) => lines,
+            None => return false,
+        };
+        let added = lines.iter().filter(|l| l.commit.is_some()).count();
+        self.log.extend(lines);
+        self.log_complete = count.is_none_or(|count| added < count);
+        added > 0
+    }
+
+    fn log_commits(&self) -> impl Iterator<Item = &Commit> {
+        self.log.iter().filter_map(|l| l.commit.as_ref())
+    }
+
+    fn selected_commit(&self) -> Option<&Commit> {
+        self.log_commits().nth(self.log_cursor)
+    }
+
+    /// The commits from the start of the log selection to the cursor, or
+    /// the one under the cursor without a selection. Newest first.
+    fn log_selection(&self) -> Vec<Commit> {
+        let anchor = self.log_anchor.unwrap_or(self.log_cursor);
+        let first = anchor.min(self.log_cursor);
+        let last = anchor.max(self.log_cursor);
+        self.log_commits()
+            .skip(first)
+            .take(last - first + 1)
+            .cloned()
+            .collect()
+    }
+
+    /// The first and last row of the log layer the selection covers
+    fn log_visual_rows(&self) -> Option<(i32, i32)> {
+        let anchor = self.log_commit_pos(self.log_anchor?)?.y;
+        let cursor = self.log_commit_pos(self.log_cursor)?.y;
+        Some((anchor.min(cursor), anchor.max(cursor)))
+    }
+
+    /// Where commit `n` of the log panel starts, after the graph
+    fn log_commit_pos(&self, n: usize) -> Option<Coord> {
+        self.log
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.commit.is_some())
+            .nth(n)
+            .map(|(i, l)| Coord::new(display_width(&l.graph) as i32, 1 + i as i32))
+    }
+
+    /// Puts the log cursor on commit `n`, or the last one there is. The
+    /// next page of commits is loaded once it gets close to the last one
+    /// loaded, going to the last one loads all of them.
+    fn log_cursor_to(&mut self, n: usize) {
+        if n == usize::MAX {
+            self.load_log_page(None);
+        } else if n.saturating_add(LOG_PAGE / 2) >= self.log_commits().count() {
+            self.load_log_page(Some(LOG_PAGE));
+        }
+        self.log_cursor = n.min(self.log_commits().count().saturating_sub(1));
+    }
+
+    /// Opens the message and diff of the commit under the log cursor
+    fn show_selected_commit(&mut self) {
+        let oid = match self.selected_commit() {
+            Some(commit) => commit.oid.clone(),
+            None => return,
+        };
+        if let Some(details) = self.report(self.git.show(&oid)) {
+            self.revision = details;
+            self.revision_scroll = 0;
+            self.revision_parent = OpenPanel::Log;
+            self.open_panel = OpenPanel::Revision;
+        }
+    }
+
+    /// The unmerged file under the cursor and how it conflicts
+    fn selected_conflict(&self) -> Option<(PathBuf, Conflict)> {
+        let entry = self.get_file()?;
+        Some((entry.path.clone(), entry.conflict?))
+    }
+
+    /// Resolves the conflict under the cursor with the version of `side`
+    fn take_side(&mut self, side: Side) {
+        if let Some((path, conflict)) = self.selected_conflict() {
+            let result = self.git.take_side(&path, side, !conflict.has(side));
+            self.report(result);
+        }
+    }
+
+    fn mergetool(&mut self) {
+        let path = match self.selected_conflict() {
+            Some((path, _)) => path,
+            None => return,
+        };
+        self.win.suspend();
+        let result = self.git.mergetool(&path);
+        self.win.resume();
+        self.report(result);
+    }
+
+    fn nothing_in_progress(&mut self) {
+        self.notification = Some(Notification::Error(String::from(
+            "No merge, rebase, cherry-pick or revert is in progress",
+        )));
+    }
+
+    /// Opens the todo list for rebasing the commit under the log cursor and
+    /// the ones after it
+    fn open_rebase(&mut self) {
+        if self.log_branch.is_some() {
+            self.notification = Some(Notification::Error(String::from(
+                "Only commits of the current branch can be rebased",
+            )));
+            return;
+        }
+        let oid = match self.selected_commit() {
+            Some(commit) => commit.oid.clone(),
+            None => return,
+        };
+        let base = match self.report(self.git.parent(&oid)) {
+            Some(base) => base,
+            None => return,
+        };
+        let todo = match self.report(self.git.rebase_todo(base.as_deref())) {
+            Some(todo) => todo,
+            None => return,
+        };
+        if todo.is_empty() {
+            self.notification = Some(Notification::Error(String::from(
+                "There are no commits to rebase, merges are left out",
+            )));
+            return;
+        }
+        self.rebase_todo = todo;
+        self.rebase_base = base;
+        self.rebase_cursor = 0;
+        self.open_panel = OpenPanel::Rebase;
+    }
+
+    fn set_todo_verb(&mut self, verb: TodoVerb) {
+        if let Some(line) = self.rebase_todo.get_mut(self.rebase_cursor) {
+            line.verb = verb;
+        }
+    }
+
+    /// Swaps the todo line under the cursor with the one below, or above,
+    /// keeping the cursor on it
+    fn move_todo_line(&mut self, down: bool) {
+        let i = self.rebase_cursor;
+        let j = match (down, i.checked_sub(1)) {
+            (true, _) => i + 1,
+            (false, Some(j)) => j,
+            (false, None) => return,
+        };
+        if j < self.rebase_todo.len() {
+            self.rebase_todo.swap(i, j);
+            self.rebase_cursor = j;
+        }
+    }
+
+    fn start_rebase(&mut self) {
+        if let Some(first) = self.rebase_todo.iter().find(|l| l.verb != TodoVerb::Drop) {
+            if first.verb.melds() {
+                self.notification = Some(Notification::Error(format!(
+                    "The first commit can't be a {}, there's nothing before it",
+                    first.verb.name()
+                )));
+                return;
+            }
+        }
+
+        self.win.suspend();
+        let result = self
+            .git
+            .rebase_interactive(self.rebase_base.as_deref(), &self.rebase_todo);
+        self.win.resume();
+        self.report(result);
+        self.open_panel = OpenPanel::Staging;
+    }
+
+    /// Cherry-picks or reverts the selected commits of the log, oldest
+    /// first when picking and newest first when reverting, so the changes
+    /// build on each other.
+    fn sequence_selected(&mut self, op: Operation) {
+        if let Some(running) = self.operation {
+            self.notification = Some(Notification::Error(format!(
+                "A {} is in progress, continue or abort it first",
+                running.command()
+            )));
+            self.open_panel = OpenPanel::Staging;
+            return;
+        }
+        let mut oids: Vec<String> = self.log_selection().into_iter().map(|c| c.oid).collect();
+        if oids.is_empty() {
+            return;
+        }
+
+        let args = match op {
+            Operation::Revert => enabled_args(REVERT_ARGS, &self.enabled_revert_args),
+            _ => {
+                oids.reverse();
+                enabled_args(CHERRY_PICK_ARGS, &self.enabled_cherry_pick_args)
+            }
+        };
+        let edit = args.iter().any(|a| a == "--edit");
+        if edit {
+            self.win.suspend();
+        }
+        let result = match op {
+            Operation::Revert => self.git.revert(args, &oids),
+            _ => self.git.cherry_pick(args, &oids),
+        };
+        if edit {
+            self.win.resume();
+        }
+        if let Some(Sequenced::Stopped) = self.report(result) {
+            self.notification = Some(Notification::Error(format!(
+                "The {} stopped, resolve the conflicts and continue",
+                op.command()
+            )));
+        }
+        self.log_anchor = None;
+        self.open_panel = OpenPanel::Staging;
+    }
+
+    fn open_reset(&mut self) {
+        self.reset_parent = self.open_panel;
+        self.open_panel = OpenPanel::Resetting;
+    }
+
+    /// The commit under the log cursor if the reset popup was opened from
+    /// the log, otherwise the one before HEAD
+    fn reset_target(&self) -> Option<String> {
+        match self.reset_parent {
+            OpenPanel::Log => self.selected_commit().map(|c| c.oid.clone()),
+            _ => Some(String::from("HEAD~1")),
+        }
+    }
+
+    /// Resets to `rev` in the enabled mode, asking first if that discards
+    /// changes
+    fn reset(&mut self, rev: &str) {
+        if self.enabled_reset_args.contains("--hard") {
+            self.pending = Some(PendingAction::HardReset(String::from(rev)));
+            self.open_panel = OpenPanel::Confirm;
+        } else {
+            self.open_panel = OpenPanel::Staging;
+            self.run_reset(rev);
+        }
+    }
+
+    /// Resets to `rev` and tells where HEAD was, so the reset can be undone
+    fn run_reset(&mut self, rev: &str) {
+        let target = match self.report(self.git.short_oid(rev)) {
+            Some(Some(oid)) => oid,
+            Some(None) => {
+                self.notification = Some(Notification::Error(format!(
+                    "{} doesn't name a commit",
+                    rev
+                )));
+                return;
+            }
+            None => return,
+        };
+        let previous = self.report(self.git.short_oid("HEAD")).flatten();
+        let args = enabled_args(RESET_ARGS, &self.enabled_reset_args);
+        if self.report(self.git.reset(args, &target)).is_none() {
+            return;
+        }
+        if let Some(previous) = previous {
+            self.notification = Some(Notification::Info(format!(
+                "Reset to {}, HEAD was at {} which the reflog keeps as HEAD@{{1}}",
+                target, previous
+            )));
+        }
+    }
+
+    fn on_stash(&self) -> bool {
+        matches!(&self.cursor, Some(item) if item.section == Section::Stashes)
+    }
+
+    /// The stash under the cursor, or the latest one if the cursor isn't
+    /// on a stash. Sets an error if there are none.
+    fn selected_stash(&mut self) -> Option<Stash> {
+        let i = match &self.cursor {
+            Some(item) if item.section == Section::Stashes => item.row.file(),
+            _ => 0,
+        };
+        let stash = self.stashes.get(i).cloned();
+        if stash.is_none() {
+            self.notification = Some(Notification::Error(String::from("There are no stashes")));
+        }
+        stash
+    }
+
+    fn stash(&mut self, msg: &str) {
+        let args = enabled_args(STASH_ARGS, &self.enabled_stash_args);
+        let result = self.git.stash(args, msg);
+        if let Some(false) = self.report(result) {
+            self.notification = Some(Notification::Info(String::from(
+                "No local changes to stash",
+            )));
+        }
+    }
+
+    /// Applies the selected stash, dropping it afterwards if `pop` is set
+    fn apply_stash(&mut self, pop: bool) {
+        let stash = match self.selected_stash() {
+            Some(stash) => stash,
+            None => return,
+        };
+        let result = self.git.stash_apply(&stash.name, pop);
+        if let Some(StashApplied::Conflicts) = self.report(result) {
+            let kept = if pop { ", it was kept" } else { "" };
+            self.notification = Some(Notification::Error(format!(
+                "Applying {} conflicted{}",
+                stash.name, kept
+            )));
+        }
+    }
+
+    /// Asks for confirmation before dropping the selected stash
+    fn drop_stash(&mut self) {
+        if let Some(stash) = self.selected_stash() {
+            self.pending = Some(PendingAction::DropStash(stash));
+            self.open_panel = OpenPanel::Confirm;
+        }
+    }
+
+    /// Opens the changes of the selected stash to the commit it was made on
+    fn show_stash(&mut self) {
+        let stash = match self.selected_stash() {
+            Some(stash) => stash,
+            None => return,
+        };
+        if let Some(details) = self.report(self.git.show(&stash.name)) {
+            self.revision = details;
+            self.revision_scroll = 0;
+            self.revision_parent = OpenPanel::Staging;
+            self.open_panel = OpenPanel::Revision;
+        }
+    }
+
+    /// Commits with a message from the user's editor, which gets the
+    /// terminal to itself while it runs
+    fn commit_in_editor(&mut self) {
+        let args = enabled_args(COMMIT_ARGS, &self.enabled_commit_args);
+        self.win.suspend();
+        let result = self.git.commit_in_editor(args);
+        self.win.resume();
+
+        match result {
+            Err(err) if err.is_empty_commit_message() => {
+                self.notification = Some(Notification::Info(String::from(
+                    "Commit aborted, the message was empty",
+                )));
+            }
+            result => {
+                self.report(result);
+            }
+        }
+        self.open_panel = OpenPanel::Staging;
+    }
+
+    /// Enables `--mainline` in the popup of `op` with the `parent` number
+    /// entered, and goes back to it. Nothing entered leaves it disabled.
+    fn enable_mainline(&mut self, op: Operation, parent: Option<&str>) {
+        let enabled = match op {
+            Operation::Revert => {
+                self.open_panel = OpenPanel::Reverting;
+                &mut self.enabled_revert_args
+            }
+            _ => {
+                self.open_panel = OpenPanel::CherryPicking;
+                &mut self.enabled_cherry_pick_args
+            }
+        };
+        match parent.map(str::parse::<u32>) {
+            None => {}
+            Some(Ok(parent)) if parent > 0 => {
+                enabled.insert(format!("--mainline={}", parent));
+            }
+            Some(_) => {
+                self.notification = Some(Notification::Error(String::from(
+                    "The parent number counts from 1",
+                )))
+            }
+        }
+    }
+
+    fn open_input(&mut self, purpose: InputPurpose) {
+        self.input_purpose = Some(purpose);
+        self.input_mode.input.clear();
+        self.open_panel = OpenPanel::Input;
     }
 
     fn cursor_move(&mut self, amount: i32) {
-        self.cursor.y += amount;
+        match self.cursor_index() {
+            Some(i) => self.cursor_to((i as i32 + amount).max(0) as usize),
+            None => self.cursor_to(0),
+        }
     }
 
-    fn get_file(&self) -> Option<&PathBuf> {
-        // Untracked file
-        if self.cursor.y >= self.fl1_pos.y
-            && self.cursor.y < self.fl1_pos.y + self.fl1_vec.len() as i32
-            && self.fl1_vec.len() > 0
-        {
-            return Some(&self.fl1_vec[(self.cursor.y - self.fl1_pos.y) as usize]);
+    /// Puts the cursor on item `i`, or the last one if there aren't as many
+    fn cursor_to(&mut self, i: usize) {
+        if let Some(item) = item_at(&self.items, i) {
+            self.cursor = Some(item.clone());
         }
-        // Staged file
-        if self.cursor.y >= self.fl2_pos.y
-            && self.cursor.y < self.fl2_pos.y + self.fl2_vec.len() as i32
-            && self.fl2_vec.len() > 0
-        {
-            return Some(&self.fl2_vec[(self.cursor.y - self.fl2_pos.y) as usize]);
+    }
+
+    fn cursor_index(&self) -> Option<usize> {
+        let cursor = self.cursor.as_ref()?;
+        self.items.iter().position(|item| item == cursor)
+    }
+
+    /// Moves the cursor to the first item of the next, or previous, section
+    /// that has any.
+    fn jump_section(&mut self, forward: bool) {
+        let i = match self.cursor_index() {
+            Some(i) => i,
+            None => return self.cursor_to(0),
+        };
+        if let Some(target) = section_jump(&self.items, i, forward) {
+            self.cursor_to(target);
         }
-        // Unstaged file
-        if self.cursor.y >= self.fl3_pos.y
-            && self.cursor.y < self.fl3_pos.y + self.fl3_vec.len() as i32
-            && self.fl3_vec.len() > 0
-        {
-            return Some(&self.fl3_vec[(self.cursor.y - self.fl3_pos.y) as usize]);
+    }
+
+    fn section_entries(&self, section: Section) -> &[StatusEntry] {
+        match section {
+            Section::Untracked => &self.fl1_vec,
+            Section::Staged => &self.fl2_vec,
+            Section::Unstaged => &self.fl3_vec,
+            Section::Unmerged => &self.unmerged_vec,
+            Section::Stashes => &[],
         }
+    }
+
+    /// The file list row under the cursor
+    fn get_row(&self) -> Option<(Section, ListRow)> {
+        self.cursor.as_ref().map(|item| (item.section, item.row))
+    }
 
-        None
+    fn get_file(&self) -> Option<&StatusEntry> {
+        let (section, row) = self.get_row()?;
+        self.section_entries(section).get(row.file())
+    }
+
+    /// Stages, or unstages if `unstage` is set, whatever is under the cursor:
+    /// a whole file, a hunk or the visually selected lines of a hunk.
+    fn stage_selection(&mut self, unstage: bool) {
+        let (section, row) = match self.get_row() {
+            Some(r) => r,
+            None => return,
+        };
+        let entry = match self.get_file() {
+            Some(e) => e.clone(),
+            None => return,
+        };
+
+        // Staging an unmerged file is how git marks it resolved
+        if section == Section::Unmerged {
+            if !unstage {
+                let result = self.git.mark_resolved(&entry.path);
+                self.report(result);
+            }
+            return;
+        }
+
+        let hunk = match row {
+            ListRow::File(_) => {
+                let result = if unstage {
+                    let mut paths: Vec<&Path> = vec![&entry.path];
+                    paths.extend(entry.orig_path.as_deref());
+                    self.git.unstage_file(&paths)
+                } else {
+                    self.git.stage_file(&entry.path)
+                };
+                self.report(result);
+                return;
+            }
+            ListRow::Hunk(_, h) | ListRow::Line(_, h, _) => h,
+        };
+
+        // Hunks can only be moved away from the side they're shown on
+        let from = if unstage {
+            Section::Staged
+        } else {
+            Section::Unstaged
+        };
+        if section != from {
+            return;
+        }
+
+        if let Some(patch) = self.selected_patch(section, entry.path, hunk, unstage) {
+            let result = self.git.apply(&patch, ApplyTo::Index, unstage);
+            self.report(result);
+        }
+    }
+
+    /// Asks for confirmation before discarding whatever is under the cursor
+    fn discard_selection(&mut self) {
+        let (section, row) = match self.get_row() {
+            Some(r) if r.0 != Section::Unmerged => r,
+            _ => return,
+        };
+        let entry = match self.get_file() {
+            Some(e) => e.clone(),
+            None => return,
+        };
+
+        let pending = match row {
+            ListRow::File(_) => PendingAction::DiscardFile(section, entry),
+            // Both the index and the work tree hold the new side of the
+            // diff, so the patch is built for reverse application
+            ListRow::Hunk(_, h) | ListRow::Line(_, h, _) => {
+                match self.selected_patch(section, entry.path.clone(), h, true) {
+                    Some(patch) => PendingAction::DiscardPatch(section, entry.path, patch),
+                    None => return,
+                }
+            }
+        };
+
+        self.pending = Some(pending);
+        self.open_panel = OpenPanel::Confirm;
+    }
+
+    fn run_pending(&mut self, pending: PendingAction) {
+        let result = match pending {
+            PendingAction::DiscardFile(Section::Untracked, entry) => self.git.clean(&entry.path),
+            PendingAction::DiscardFile(section, entry) => {
+                let staged = section == Section::Staged;
+                self.git.restore(&entry.restore_paths(staged), staged)
+            }
+            PendingAction::DiscardPatch(section, _, patch) => {
+                let target = if section == Section::Staged {
+                    ApplyTo::Both
+                } else {
+                    ApplyTo::WorkTree
+                };
+                self.git.apply(&patch, target, true)
+            }
+            PendingAction::ForceDeleteBranch(branch) => {
+                let result = self.git.delete_branch(&branch, true);
+                self.report(result);
+                self.load_branches();
+                return;
+            }
+            PendingAction::DropStash(stash) => self.git.stash_drop(&stash.name),
+            PendingAction::AbortOperation(op) => self.git.abort_operation(op),
+            PendingAction::HardReset(rev) => {
+                self.run_reset(&rev);
+                return;
+            }
+            PendingAction::PushSetUpstream(remote, branch) => {
+                let mut args = enabled_args(PUSH_ARGS, &self.enabled_push_args);
+                args.retain(|a| a != "--set-upstream");
+                args.push(String::from("--set-upstream"));
+                self.push(args, &[&remote, &branch]);
+                return;
+            }
+        };
+        self.report(result);
+    }
+
+    /// The patch for the hunk, or the visually selected lines of it, under
+    /// the cursor. See `FileDiff::hunk_patch`.
+    fn selected_patch(
+        &mut self,
+        section: Section,
+        path: PathBuf,
+        hunk: usize,
+        reverse: bool,
+    ) -> Option<Vec<u8>> {
+        let selection = match (self.visual_anchor, self.visual_selection()) {
+            (Some(_), None) => {
+                self.notification = Some(Notification::Error(String::from(
+                    "Selected lines have to be within a single hunk",
+                )));
+                return None;
+            }
+            (_, selection) => selection,
+        };
+        self.visual_anchor = None;
+
+        self.diffs
+            .get(&(section, path))
+            .and_then(|d| d.hunk_patch(hunk, selection, reverse))
+    }
+
+    /// The hunk lines between the visual anchor and the cursor, if they are
+    /// in the same hunk.
+    fn visual_selection(&self) -> Option<RangeInclusive<usize>> {
+        match (self.visual_anchor?, self.get_row()?) {
+            ((s1, ListRow::Line(f1, h1, l1)), (s2, ListRow::Line(f2, h2, l2)))
+                if s1 == s2 && f1 == f2 && h1 == h2 =>
+            {
+                Some(l1.min(l2)..=l1.max(l2))
+            }
+            _ => None,
+        }
+    }
+
+    /// First and last status row covered by the visual selection
+    fn visual_rows(&self) -> Option<(i32, i32)> {
+        let (section, anchor) = self.visual_anchor?;
+        let y = self
+            .items
+            .iter()
+            .find(|item| item.section == section && item.row == anchor)?
+            .y;
+        let cursor = self.cursor.as_ref()?.y;
+        Some((y.min(cursor), y.max(cursor)))
+    }
+
+    fn toggle_expand(&mut self) {
+        let (section, row) = match self.get_row() {
+            Some(r) if r.0 == Section::Staged || r.0 == Section::Unstaged => r,
+            _ => return,
+        };
+        let path = self.section_entries(section)[row.file()].path.clone();
+        self.visual_anchor = None;
+        if !self.expanded.remove(&(section, path.clone())) {
+            self.expanded.insert((section, path));
+        }
+
+        // Collapsing from inside the diff would otherwise leave the cursor
+        // on a row that's gone
+        if let Some(cursor) = &mut self.cursor {
+            cursor.row = ListRow::File(row.file());
+        }
     }
 
     fn update_status_layer(&mut self) {
         self.status_layer = Layer::new();
+        self.status = self.report(self.git.status()).unwrap_or_default();
 
         let mut branch_title: Text = UiElement::new();
         let mut branch_name: Text = UiElement::new();
         let mut last_commit_msg: Text = UiElement::new();
+        let mut upstream_title: Text = UiElement::new();
+        let mut upstream_name: Text = UiElement::new();
         let mut fl1: FileList = UiElement::new();
         let mut fl2: FileList = UiElement::new();
         let mut fl3: FileList = UiElement::new();
         let mut untracked_header: ListHeader = UiElement::new();
         let mut staged_header: ListHeader = UiElement::new();
         let mut unstaged_header: ListHeader = UiElement::new();
+        let mut stash_header: ListHeader = UiElement::new();
+        let mut stash_list: StashList = UiElement::new();
+        let mut operation_title: Text = UiElement::new();
+        let mut operation_keys: Text = UiElement::new();
+        let mut unmerged_header: ListHeader = UiElement::new();
+        let mut unmerged: FileList = UiElement::new();
+
+        // There is no commit to describe in a fresh repository
+        if self.status.branch.oid.is_some() {
+            last_commit_msg.content = self.report(self.git.last_commit_msg()).unwrap_or_default();
+        }
 
+        let branch = &self.status.branch;
         branch_title.content = String::from("Head:    ");
-        branch_name.content = self.git.branch_name();
+        branch_name.content = match (&branch.head, &branch.oid) {
+            (Some(head), _) => head.clone(),
+            (None, Some(oid)) => String::from(oid.get(..7).unwrap_or(oid)),
+            (None, None) => String::from("(detached)"),
+        };
         branch_name.c_pair = COLOR_PAIR_H3;
-        last_commit_msg.content = self.git.last_commit_msg();
 
-        fl1.files = self.git.untracked();
+        if let Some(upstream) = &branch.upstream {
+            upstream_title.content = String::from("Merge:   ");
+            upstream_name.content = upstream.clone();
+            if branch.ahead > 0 || branch.behind > 0 {
+                upstream_name.content += &format!(" (+{}/-{})", branch.ahead, branch.behind);
+            }
+            upstream_name.c_pair = COLOR_PAIR_H3;
+        }
+
+        self.fl1_vec = self.status.untracked().cloned().collect();
+        self.fl2_vec = self.status.staged().cloned().collect();
+        self.fl3_vec = self.status.unstaged().cloned().collect();
+        self.unmerged_vec = self.status.unmerged().cloned().collect();
+        self.operation = self.report(self.git.operation()).unwrap_or_default();
+        self.stashes = self.report(self.git.stashes()).unwrap_or_default();
+
+        let status = &self.status;
+        self.expanded.retain(|(section, path)| match section {
+            Section::Staged => status.staged().any(|e| &e.path == path),
+            Section::Unstaged => status.unstaged().any(|e| &e.path == path),
+            Section::Untracked | Section::Unmerged | Section::Stashes => false,
+        });
+
+        fl1.files = self.fl1_vec.iter().map(|e| e.path.clone()).collect();
         fl1.c_pair = COLOR_PAIR_UNTRACKED;
-        fl2.files = self.git.staged();
+        fl2.files = self.fl2_vec.iter().map(|e| e.path.clone()).collect();
+        self.diffs.clear();
+        fl2.diffs = self.expanded_diffs(Section::Staged);
         fl2.style = TextStyle::BOLD;
-        fl3.files = self.git.unstaged();
+        fl2.c_pair = COLOR_PAIR_STAGED;
+        fl3.files = self.fl3_vec.iter().map(|e| e.path.clone()).collect();
+        // Changes inside a submodule only show up in its work tree
+        fl3.notes = self
+            .fl3_vec
+            .iter()
+            .map(|e| e.submodule.map(|s| s.describe()).unwrap_or_default())
+            .collect();
+        fl3.diffs = self.expanded_diffs(Section::Unstaged);
         fl3.style = TextStyle::BOLD;
+        fl3.c_pair = COLOR_PAIR_UNSTAGED;
+
+        self.fl1_rows = fl1.rows();
+        self.fl2_rows = fl2.rows();
+        self.fl3_rows = fl3.rows();
 
         untracked_header.set_title(String::from("Untracked Files"));
-        untracked_header.set_amount(fl1.size().y);
+        untracked_header.set_amount(fl1.len() as i32);
         staged_header.set_title(String::from("Staged changes"));
-        staged_header.set_amount(fl2.size().y);
+        staged_header.set_amount(fl2.len() as i32);
         unstaged_header.set_title(String::from("Unstaged changes"));
-        unstaged_header.set_amount(fl3.size().y);
+        unstaged_header.set_amount(fl3.len() as i32);
+        if let Some(op) = self.operation {
+            let step = match op {
+                Operation::Rebase => self.report(self.git.rebase_step()).flatten(),
+                _ => None,
+            };
+            let todo = match op {
+                Operation::CherryPick | Operation::Revert => {
+                    self.report(self.git.sequencer_todo()).unwrap_or_default()
+                }
+                _ => Vec::new(),
+            };
+            let name = match op {
+                Operation::Merge => "Merging",
+                Operation::Rebase => "Rebasing",
+                Operation::ApplyMailbox => "Applying patches",
+                Operation::CherryPick => "Cherry-picking",
+                Operation::Revert => "Reverting",
+            };
+            let title = match (step, todo.as_slice()) {
+                (Some((step, end)), _) => format!("{} {}/{}", name, step, end),
+                (None, [line]) => format!("{} {}", name, line.oid),
+                (None, [line, rest @ ..]) => {
+                    format!("{} {}, {} more", name, line.oid, rest.len())
+                }
+                (None, []) => String::from(name),
+            };
+            operation_title.content = format!("{:<8}", title);
+            operation_title.c_pair = COLOR_PAIR_H2;
+            let skip = if op.can_skip() {
+                format!(
+                    ", {} skips",
+                    chord_for(&self.stage_mode, Action::SkipOperation)
+                )
+            } else {
+                String::new()
+            };
+            operation_keys.content = format!(
+                "{} continues{}, {} aborts",
+                chord_for(&self.stage_mode, Action::ContinueOperation),
+                skip,
+                chord_for(&self.stage_mode, Action::AbortOperation),
+            );
+        }
 
-        self.fl1_vec = self.git.untracked();
-        self.fl2_vec = self.git.staged();
-        self.fl3_vec = self.git.unstaged();
+        unmerged.files = self.unmerged_vec.iter().map(|e| e.path.clone()).collect();
+        unmerged.labels = self
+            .unmerged_vec
+            .iter()
+            .map(|e| match e.conflict {
+                Some(conflict) => format!("{}:", conflict.describe()),
+                None => String::new(),
+            })
+            .collect();
+        unmerged.style = TextStyle::BOLD;
+        unmerged.c_pair = COLOR_PAIR_H2;
+        self.unmerged_rows = unmerged.rows();
+        unmerged_header.set_title(String::from("Unmerged paths"));
+        unmerged_header.set_amount(unmerged.len() as i32);
+
+        stash_header.set_title(String::from("Stashes"));
+        stash_header.set_amount(self.stashes.len() as i32);
+        stash_list.stashes = self.stashes.clone();
 
         let s1 = branch_title.size();
         let s2 = branch_name.size();
-        let s3 = Coord::new(0, 4 + fl1.size().y);
+        let operation_x = s1.x.max(operation_title.size().x + 1);
+        let mut top = 1 + upstream_title.size().y;
+        let operation_y = top;
+        top += operation_title.size().y;
+        // Only there while something conflicts
+        let unmerged_y = top + 1;
+        self.unmerged_pos = Coord::new(2, unmerged_y + 1);
+        if unmerged.len() > 0 {
+            top += 2 + unmerged.size().y;
+        }
+        let s3 = Coord::new(0, top + 3 + fl1.size().y);
         let s4 = Coord::new(0, s3.y + 2 + fl2.size().y);
 
-        println!("{}", fl1.size().y);
-
-        self.fl1_pos = Coord::new(2, 3);
+        self.fl1_pos = Coord::new(2, top + 2);
         self.fl2_pos = Coord::new(2, 1 + s3.y);
         self.fl3_pos = s4 + Coord::new(2, 1);
+        let s5 = Coord::new(0, self.fl3_pos.y + 1 + fl3.size().y);
+        self.stash_pos = s5 + Coord::new(2, 1);
 
         self.status_layer
             .push(Box::new(branch_title), Coord::new(0, 0));
         self.status_layer
             .push(Box::new(branch_name), Coord::new(s1.x, 0));
         self.status_layer
-            .push(Box::new(last_commit_msg), Coord::new(s1.x + s2.x, 0));
+            .push(Box::new(last_commit_msg), Coord::new(s1.x + s2.x + 1, 0));
+        self.status_layer
+            .push(Box::new(upstream_title), Coord::new(0, 1));
+        self.status_layer
+            .push(Box::new(upstream_name), Coord::new(s1.x, 1));
         self.status_layer
-            .push(Box::new(untracked_header), Coord::new(0, 2));
+            .push(Box::new(operation_title), Coord::new(0, operation_y));
+        self.status_layer.push(
+            Box::new(operation_keys),
+            Coord::new(operation_x, operation_y),
+        );
+        if unmerged.len() > 0 {
+            self.status_layer
+                .push(Box::new(unmerged_header), Coord::new(0, unmerged_y));
+            self.status_layer
+                .push(Box::new(unmerged), self.unmerged_pos);
+        }
+        self.status_layer
+            .push(Box::new(untracked_header), Coord::new(0, top + 1));
         self.status_layer.push(Box::new(fl1), self.fl1_pos);
         self.status_layer.push(Box::new(staged_header), s3);
         self.status_layer.push(Box::new(fl2), self.fl2_pos);
         self.status_layer.push(Box::new(unstaged_header), s4);
         self.status_layer.push(Box::new(fl3), self.fl3_pos);
+        self.status_layer.push(Box::new(stash_header), s5);
+        self.status_layer.push(Box::new(stash_list), self.stash_pos);
+
+        let old_items = std::mem::take(&mut self.items);
+        self.items = [
+            (
+                Section::Unmerged,
+                self.unmerged_pos,
+                &self.unmerged_rows,
+                &self.unmerged_vec,
+            ),
+            (
+                Section::Untracked,
+                self.fl1_pos,
+                &self.fl1_rows,
+                &self.fl1_vec,
+            ),
+            (Section::Staged, self.fl2_pos, &self.fl2_rows, &self.fl2_vec),
+            (
+                Section::Unstaged,
+                self.fl3_pos,
+                &self.fl3_rows,
+                &self.fl3_vec,
+            ),
+        ]
+        .iter()
+        .flat_map(|&(section, pos, rows, entries)| {
+            rows.iter().enumerate().map(move |(i, row)| Item {
+                section,
+                path: entries[row.file()].path.clone(),
+                row: *row,
+                y: pos.y + i as i32,
+            })
+        })
+        .collect();
+        let stash_y = self.stash_pos.y;
+        self.items
+            .extend(self.stashes.iter().enumerate().map(|(i, stash)| Item {
+                section: Section::Stashes,
+                path: PathBuf::from(&stash.name),
+                row: ListRow::File(i),
+                y: stash_y + i as i32,
+            }));
+        self.cursor = self
+            .cursor
+            .as_ref()
+            .and_then(|cursor| find_cursor(&self.items, &old_items, cursor))
+            .or_else(|| self.items.first())
+            .cloned();
     }
 
-    fn update_pre_commit_layer(&mut self) {
-        self.pre_commit_layer = Layer::new();
+    /// Diff views for the expanded files of `section`, keyed by their index
+    /// in the section's file list.
+    fn expanded_diffs(&mut self, section: Section) -> HashMap<usize, DiffView> {
+        let paths: Vec<(usize, PathBuf)> = self
+            .section_entries(section)
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| self.expanded.contains(&(section, e.path.clone())))
+            .map(|(i, e)| (i, e.path.clone()))
+            .collect();
+
+        let mut diffs = HashMap::new();
+        for (i, path) in paths {
+            let result = self.git.diff(&path, section == Section::Staged);
+            if let Some(diff) = self.report(result) {
+                self.diffs.insert((section, path), diff.clone());
+                let mut view: DiffView = UiElement::new();
+                view.diff = diff;
+                diffs.insert(i, view);
+            }
+        }
+        diffs
+    }
+
+    /// A popup listing the toggleable `args` followed by groups of actions
+    /// under their headers, with the chords `mode` binds them to.
+    fn popup_layer(
+        &self,
+        mode: &dyn Mode,
+        args: &[PopupArg],
+        enabled: &HashSet<String>,
+        groups: &[(&str, &[(Action, &str)])],
+    ) -> Layer {
+        let mut layer = Layer::new();
 
         let mut separator: Text = UiElement::new();
         let mut arg_header: Text = UiElement::new();
         let mut arg_list: ArgList = UiElement::new();
-        let mut commit_header: Text = UiElement::new();
-        let mut commit_c: Text = UiElement::new();
-        let mut commit_text: Text = UiElement::new();
 
-        separator.content = String::from("=".repeat(self.win.get_size().x as usize));
+        separator.content = "=".repeat(self.win.get_size().x as usize);
         separator.c_pair = COLOR_PAIR_SEP;
         arg_header.content = String::from("Arguments");
         arg_header.c_pair = COLOR_PAIR_H3;
-        commit_header.content = String::from("Create");
-        commit_header.c_pair = COLOR_PAIR_H3;
-        commit_c.content = String::from("c");
-        commit_c.c_pair = COLOR_PAIR_UNTRACKED;
-        commit_text.content = String::from("Commit");
-
-        arg_list.push_arg("-a", "Stage all modified and deleted files", "--all");
-        arg_list.push_arg("-e", "Allow empty commit", "--allow-empty");
-        arg_list.push_arg("-v", "Show diff of changes to be commited", "--verbose");
-        arg_list.push_arg("-n", "Disable hooks", "--no-verify");
-        arg_list.push_arg(
-            "-R",
-            "Claim authorship and reset author date",
-            "--reset-author",
+
+        for (action, desc, flag) in args {
+            let chord = chord_for(mode, *action);
+            let on = enabled_flag(enabled, flag);
+            arg_list.push_arg(&chord, desc, on.map_or(flag, |f| f.as_str()), on.is_some());
+        }
+
+        layer.push(Box::new(separator), Coord::new(0, 0));
+        let mut y = 2;
+        if !args.is_empty() {
+            y += 2 + arg_list.size().y;
+            layer.push(Box::new(arg_header), Coord::new(0, 2));
+            layer.push(Box::new(arg_list), Coord::new(1, 3));
+        }
+
+        for (title, actions) in groups {
+            let mut header: Text = UiElement::new();
+            header.content = String::from(*title);
+            header.c_pair = COLOR_PAIR_H3;
+            layer.push(Box::new(header), Coord::new(0, y));
+            y += 1;
+
+            let mut list: KeyList = UiElement::new();
+            for (action, desc) in actions.iter() {
+                list.push_key(&chord_for(mode, *action), desc);
+            }
+            let height = list.size().y;
+            layer.push(Box::new(list), Coord::new(1, y));
+            y += height + 1;
+        }
+
+        layer
+    }
+
+    fn update_pre_commit_layer(&mut self) {
+        self.pre_commit_layer = self.popup_layer(
+            &self.commit_mode,
+            COMMIT_ARGS,
+            &self.enabled_commit_args,
+            &[
+                (
+                    "Create",
+                    &[
+                        (Action::OpenCommitMsgMode, "Commit"),
+                        (Action::CommitInEditor, "Commit in $EDITOR"),
+                    ],
+                ),
+                (
+                    "Edit HEAD",
+                    &[
+                        (Action::CommitExtend, "Extend"),
+                        (Action::CommitReword, "Reword"),
+                        (Action::CommitAmend, "Amend"),
+                    ],
+                ),
+                (
+                    "Edit",
+                    &[
+                        (Action::CommitFixup, "Fixup"),
+                        (Action::CommitSquash, "Squash"),
+                    ],
+                ),
+            ],
         );
+    }
 
-        for arg in self.enabled_commit_args.iter() {
-            arg_list.toggle(&(arg.clone()));
+    fn update_pull_layer(&mut self) {
+        let upstream = match &self.status.branch.upstream {
+            Some(upstream) => upstream.clone(),
+            None => String::from("upstream"),
+        };
+        self.pull_layer = self.popup_layer(
+            &self.pull_mode,
+            PULL_ARGS,
+            &self.enabled_pull_args,
+            &[(
+                "Pull into current branch from",
+                &[
+                    (Action::PullUpstream, &upstream),
+                    (Action::PullElsewhere, "elsewhere"),
+                ],
+            )],
+        );
+    }
+
+    fn update_push_layer(&mut self) {
+        let upstream = match &self.status.branch.upstream {
+            Some(upstream) => upstream.clone(),
+            None => String::from("upstream"),
+        };
+        self.push_layer = self.popup_layer(
+            &self.push_mode,
+            PUSH_ARGS,
+            &self.enabled_push_args,
+            &[(
+                "Push current branch to",
+                &[
+                    (Action::Push, &upstream),
+                    (Action::PushElsewhere, "elsewhere"),
+                ],
+            )],
+        );
+    }
+
+    fn update_stash_layer(&mut self) {
+        let target = match &self.cursor {
+            Some(item) if item.section == Section::Stashes => item.path.display().to_string(),
+            _ => String::from("stash@{0}"),
+        };
+        self.stash_layer = self.popup_layer(
+            &self.stash_mode,
+            STASH_ARGS,
+            &self.enabled_stash_args,
+            &[
+                ("Stash", &[(Action::Stash, "Local changes")]),
+                (
+                    &format!("Use {}", target),
+                    &[
+                        (Action::StashApply, "Apply"),
+                        (Action::StashPop, "Pop"),
+                        (Action::StashDrop, "Drop"),
+                        (Action::StashShow, "Show"),
+                    ],
+                ),
+            ],
+        );
+    }
+
+    /// What the cherry-pick and revert popups act on, the selected commit
+    /// or how many are selected
+    fn log_selection_name(&self) -> String {
+        match self.log_selection().as_slice() {
+            [commit] => commit.oid.clone(),
+            commits => format!("{} commits", commits.len()),
         }
+    }
 
-        self.pre_commit_layer
-            .push(Box::new(separator), Coord::new(0, 0));
-        self.pre_commit_layer
-            .push(Box::new(arg_header), Coord::new(0, 2));
-        self.pre_commit_layer.push(
-            Box::new(commit_header),
-            Coord::new(0, 4 + arg_list.size().y),
+    fn update_cherry_pick_layer(&mut self) {
+        let head = match &self.status.branch.head {
+            Some(branch) => branch.clone(),
+            None => String::from("HEAD"),
+        };
+        self.cherry_pick_layer = self.popup_layer(
+            &self.cherry_pick_mode,
+            CHERRY_PICK_ARGS,
+            &self.enabled_cherry_pick_args,
+            &[(
+                &format!("Apply {} to {}", self.log_selection_name(), head),
+                &[(Action::CherryPick, "Cherry-pick")],
+            )],
+        );
+    }
+
+    fn update_revert_layer(&mut self) {
+        let head = match &self.status.branch.head {
+            Some(branch) => branch.clone(),
+            None => String::from("HEAD"),
+        };
+        self.revert_layer = self.popup_layer(
+            &self.revert_mode,
+            REVERT_ARGS,
+            &self.enabled_revert_args,
+            &[(
+                &format!("Undo {} on {}", self.log_selection_name(), head),
+                &[(Action::Revert, "Revert")],
+            )],
         );
-        self.pre_commit_layer
-            .push(Box::new(commit_c), Coord::new(1, 5 + arg_list.size().y));
-        self.pre_commit_layer
-            .push(Box::new(commit_text), Coord::new(3, 5 + arg_list.size().y));
-        self.pre_commit_layer
-            .push(Box::new(arg_list), Coord::new(1, 3));
+    }
+
+    fn update_reset_layer(&mut self) {
+        let head = match &self.status.branch.head {
+            Some(branch) => branch.clone(),
+            None => String::from("HEAD"),
+        };
+        let target = self.reset_target().unwrap_or_default();
+        self.reset_layer = self.popup_layer(
+            &self.reset_mode,
+            RESET_ARGS,
+            &self.enabled_reset_args,
+            &[(
+                &format!("Reset {} to", head),
+                &[
+                    (Action::Reset, &target),
+                    (Action::ResetElsewhere, "elsewhere"),
+                ],
+            )],
+        );
+    }
 
-        // TODO: Add popup for entering commit message
+    fn update_fetch_layer(&mut self) {
+        self.fetch_layer = self.popup_layer(
+            &self.fetch_mode,
+            FETCH_ARGS,
+            &self.enabled_fetch_args,
+            &[(
+                "Fetch from",
+                &[
+                    (Action::FetchUpstream, "the current branch's remote"),
+                    (Action::FetchElsewhere, "elsewhere"),
+                ],
+            )],
+        );
     }
 
     fn update_commit_msg_layer(&mut self) {
         self.commit_msg_layer = Layer::new();
 
         let mut header: Text = UiElement::new();
-        let mut message: Text = UiElement::new();
+        let mut summary: Text = UiElement::new();
+        let mut prompt: Text = UiElement::new();
         let mut fl1: FileList = UiElement::new();
         let mut changes_header: Text = UiElement::new();
 
-        header.content = String::from("Please enter the commit message for your changes.\n >  ");
+        let prompt_msg = match self.commit_kind {
+            CommitKind::Amend => "Please enter the message of the amended commit.",
+            CommitKind::Reword => "Please enter the new message of the last commit.",
+            _ => "Please enter the commit message for your changes.",
+        };
+        header.content = format!(
+            "{} {} commits, {} goes back.",
+            prompt_msg,
+            chord_for(&self.commit_msg_mode, Action::ConfirmCommitMsg),
+            chord_for(&self.commit_msg_mode, Action::Exit),
+        );
         header.c_pair = COLOR_PAIR_H3;
-        message.content = self.commit_msg_mode.commit_msg.clone();
-        message.c_pair = COLOR_PAIR_H1;
-        if self.enabled_commit_args.contains("-a") {
-            fl1.files = [self.git.staged(), self.git.unstaged()].concat();
+
+        // The summary should fit in 50 columns and has to in 72, as should
+        // the lines of the body
+        let lines = self.commit_msg_mode.buffer.lines();
+        let summary_len = lines[0].chars().count();
+        summary.content = format!("Summary {}/{}", summary_len, SUMMARY_WIDTH);
+        summary.c_pair = if summary_len <= SUMMARY_WIDTH {
+            COLOR_PAIR_H1
+        } else if summary_len <= BODY_WIDTH {
+            COLOR_PAIR_ENABLED
         } else {
-            fl1.files = self.git.staged();
+            COLOR_PAIR_H2
+        };
+        let long_line = lines
+            .iter()
+            .skip(1)
+            .position(|l| l.chars().count() > BODY_WIDTH);
+        if let Some(i) = long_line {
+            summary.content += &format!(", line {} is longer than {}", i + 2, BODY_WIDTH);
         }
+        prompt.content = String::from(" >");
+        prompt.c_pair = COLOR_PAIR_H3;
+
+        // Long lines are wrapped to the width of the screen
+        let x = 3;
+        let width = (self.win.get_size().x - x).max(1) as usize;
+        let (cursor_row, cursor_col) = self.commit_msg_mode.buffer.cursor();
+        let mut y = 3;
+        for (row, line) in lines.iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            let starts = wrap(line, width);
+            for (i, start) in starts.iter().enumerate() {
+                let end = starts.get(i + 1).copied().unwrap_or(chars.len());
+                let mut text: Text = UiElement::new();
+                text.content = chars[*start..end].iter().collect();
+                text.c_pair = COLOR_PAIR_H1;
+                if row == cursor_row
+                    && cursor_col >= *start
+                    && (cursor_col < end || i + 1 == starts.len())
+                {
+                    let col: usize = chars[*start..cursor_col]
+                        .iter()
+                        .map(|c| char_width(*c))
+                        .sum();
+                    self.commit_msg_cursor = match col {
+                        col if col < width => Coord::new(x + col as i32, y),
+                        _ => Coord::new(x, y + 1),
+                    };
+                }
+                self.commit_msg_layer.push(Box::new(text), Coord::new(x, y));
+                y += 1;
+            }
+        }
+
+        let stage_all = self.enabled_commit_args.contains("--all");
+        fl1.files = self
+            .status
+            .entries
+            .iter()
+            .filter(|e| e.is_staged() || (stage_all && e.is_unstaged()))
+            .map(|e| e.path.clone())
+            .collect();
         fl1.c_pair = COLOR_PAIR_UNTRACKED;
         changes_header.content = String::from("Changed to be committed:");
         changes_header.c_pair = COLOR_PAIR_H3;
@@ -469,10 +2878,16 @@ impl Controller {
         self.commit_msg_layer
             .push(Box::new(header), Coord::new(0, 0));
         self.commit_msg_layer
-            .push(Box::new(message), Coord::new(3, 1));
-        self.commit_msg_layer.push(Box::new(fl1), Coord::new(1, 4));
+            .push(Box::new(summary), Coord::new(0, 1));
         self.commit_msg_layer
-            .push(Box::new(changes_header), Coord::new(0, 3));
+            .push(Box::new(prompt), Coord::new(0, 3));
+        // Rewording leaves the changes out
+        if self.commit_kind != CommitKind::Reword {
+            self.commit_msg_layer
+                .push(Box::new(changes_header), Coord::new(0, y + 1));
+            self.commit_msg_layer
+                .push(Box::new(fl1), Coord::new(1, y + 2));
+        }
     }
 
     fn update_help_layer(&mut self) {
@@ -480,7 +2895,7 @@ impl Controller {
         let mut header: Text = UiElement::new();
         let mut list: KeyList = UiElement::new();
 
-        separator.content = String::from("=".repeat(self.win.get_size().x as usize));
+        separator.content = "=".repeat(self.win.get_size().x as usize);
         separator.c_pair = COLOR_PAIR_SEP;
         header.content = String::from("Staging");
         header.c_pair = COLOR_PAIR_H3;
@@ -496,14 +2911,268 @@ impl Controller {
         self.help_layer.push(Box::new(list), Coord::new(1, 3));
     }
 
-    fn render_push_start(&self) {
-        let pos = Coord::new(0, self.fl3_pos.y + self.fl3_vec.len() as i32 + 1);
-        let mut push_msg: Text = UiElement::new();
-        push_msg.content = String::from("Pushing...");
-        push_msg.style = TextStyle::BOLD;
-        push_msg.c_pair = COLOR_PAIR_SELECTED;
+    fn update_branch_layer(&mut self) {
+        self.branch_layer = Layer::new();
+
+        let mut local_header: ListHeader = UiElement::new();
+        let mut local_list: BranchList = UiElement::new();
+        let mut remote_header: ListHeader = UiElement::new();
+        let mut remote_list: BranchList = UiElement::new();
+
+        let (remotes, locals) = self.branches.iter().cloned().partition(|b| b.remote);
+        local_list.branches = locals;
+        remote_list.branches = remotes;
+        local_header.set_title(String::from("Branches"));
+        local_header.set_amount(local_list.branches.len() as i32);
+        remote_header.set_title(String::from("Remote branches"));
+        remote_header.set_amount(remote_list.branches.len() as i32);
+
+        let s1 = local_list.size();
+        self.branch_layer
+            .push(Box::new(local_header), Coord::new(0, 0));
+        self.branch_layer
+            .push(Box::new(local_list), Coord::new(0, 1));
+        self.branch_layer
+            .push(Box::new(remote_header), Coord::new(0, s1.y + 2));
+        self.branch_layer
+            .push(Box::new(remote_list), Coord::new(0, s1.y + 3));
+
+        self.branch_keys_layer = self.popup_layer(
+            &self.branch_mode,
+            &[],
+            &HashSet::new(),
+            &[(
+                "Branch",
+                &[
+                    (Action::CheckoutBranch, "Checkout"),
+                    (Action::CreateBranch, "Create and checkout from here"),
+                    (Action::RenameBranch, "Rename"),
+                    (Action::DeleteBranch, "Delete"),
+                    (Action::OpenLogMode, "Log"),
+                ],
+            )],
+        );
+    }
 
-        push_msg.render(&self.win.win, pos);
-        self.win.render();
+    fn update_pick_layer(&mut self) {
+        self.pick_layer = Layer::new();
+
+        let mut header: ListHeader = UiElement::new();
+        let mut list: CommitList = UiElement::new();
+        header.set_title(String::from("Commits"));
+        header.set_amount(self.pick_commits.len() as i32);
+        list.commits = self.pick_commits.clone();
+
+        self.pick_layer.push(Box::new(header), Coord::new(0, 0));
+        self.pick_layer.push(Box::new(list), Coord::new(2, 1));
+
+        let (title, desc) = match self.commit_kind {
+            CommitKind::Squash => ("Squash", "Create a squash! commit for this one"),
+            _ => ("Fixup", "Create a fixup! commit for this one"),
+        };
+        self.pick_keys_layer = self.popup_layer(
+            &self.commit_pick_mode,
+            &[],
+            &HashSet::new(),
+            &[(title, &[(Action::Confirm, desc), (Action::Exit, "Cancel")])],
+        );
+    }
+
+    fn update_rebase_layer(&mut self) {
+        self.rebase_layer = Layer::new();
+
+        let mut header: ListHeader = UiElement::new();
+        let mut keys: Text = UiElement::new();
+        let mut list: TodoList = UiElement::new();
+        header.set_title(match &self.rebase_base {
+            Some(base) => format!("Rebase onto {}, oldest first", base),
+            None => String::from("Rebase from the root, oldest first"),
+        });
+        header.set_amount(self.rebase_todo.len() as i32);
+        let chord = |action| chord_for(&self.rebase_mode, action);
+        keys.content = format!(
+            "{} pick, {} reword, {} edit, {} squash, {} fixup, {} drop, {}/{} move, {} starts, {} cancels",
+            chord(Action::RebasePick),
+            chord(Action::RebaseReword),
+            chord(Action::RebaseEdit),
+            chord(Action::RebaseSquash),
+            chord(Action::RebaseFixup),
+            chord(Action::RebaseDrop),
+            chord(Action::RebaseMoveUp),
+            chord(Action::RebaseMoveDown),
+            chord(Action::Confirm),
+            chord(Action::Exit),
+        );
+        list.lines = self.rebase_todo.clone();
+
+        self.rebase_layer.push(Box::new(header), Coord::new(0, 0));
+        self.rebase_layer.push(Box::new(keys), Coord::new(0, 1));
+        self.rebase_layer.push(Box::new(list), Coord::new(2, 2));
+    }
+
+    fn update_log_layer(&mut self) {
+        self.log_layer = Layer::new();
+
+        let mut header: ListHeader = UiElement::new();
+        let mut list: LogList = UiElement::new();
+        let head = match (&self.log_branch, &self.status.branch.head) {
+            (Some(branch), _) | (None, Some(branch)) => branch.clone(),
+            (None, None) => String::from("HEAD"),
+        };
+        header.set_title(format!("Commits on {}", head));
+        header.set_amount(self.log_commits().count() as i32);
+        list.lines = self.log.clone();
+
+        self.log_layer.push(Box::new(header), Coord::new(0, 0));
+        self.log_layer.push(Box::new(list), Coord::new(0, 1));
+    }
+
+    /// The message of the opened commit followed by the diff of every file
+    /// it changed
+    fn update_revision_layer(&mut self) {
+        self.revision_layer = Layer::new();
+
+        let details = &self.revision;
+        let mut lines = vec![
+            (format!("commit {}", details.oid), COLOR_PAIR_H3),
+            (format!("Author: {}", details.author), COLOR_PAIR_DEFAULT),
+            (format!("Date:   {}", details.date), COLOR_PAIR_DEFAULT),
+            (String::new(), COLOR_PAIR_DEFAULT),
+        ];
+        lines.extend(
+            details
+                .message
+                .lines()
+                .map(|l| (format!("    {}", l), COLOR_PAIR_DEFAULT)),
+        );
+
+        let mut files: FileList = UiElement::new();
+        for (i, diff) in details.diffs.iter().enumerate() {
+            files
+                .files
+                .push(PathBuf::from(diff.path().unwrap_or_default()));
+            files.diffs.insert(i, DiffView { diff: diff.clone() });
+        }
+        files.style = TextStyle::BOLD;
+
+        let y = lines.len() as i32 + 1;
+        for (i, (content, c_pair)) in lines.into_iter().enumerate() {
+            let mut text: Text = UiElement::new();
+            text.content = content;
+            text.c_pair = c_pair;
+            self.revision_layer
+                .push(Box::new(text), Coord::new(0, i as i32));
+        }
+        self.revision_layer.push(Box::new(files), Coord::new(0, y));
+    }
+
+    fn update_job_layer(&mut self) {
+        self.job_layer = Layer::new();
+
+        let running = match &self.job {
+            Some(running) => running,
+            None => return,
+        };
+        let mut bar: ProgressBar = UiElement::new();
+        let label = match running.kind {
+            JobKind::Fetch => "Fetching...",
+            JobKind::Pull => "Pulling...",
+            JobKind::Push => "Pushing...",
+        };
+        bar.progress = running.progress.clone();
+        bar.label = match bar.progress {
+            Some(_) => String::from(label),
+            None => format!("{} {}", label, running.last_line),
+        };
+
+        self.job_layer.push(Box::new(bar), Coord::new(0, 0));
+    }
+
+    fn update_notification_layer(&mut self) {
+        self.notification_layer = Layer::new();
+
+        let (msg, c_pair) = match &self.notification {
+            Some(Notification::Info(msg)) => (msg, COLOR_PAIR_H1),
+            Some(Notification::Error(msg)) => (msg, COLOR_PAIR_H2),
+            None => return,
+        };
+
+        for (i, line) in msg.lines().enumerate() {
+            let mut text: Text = UiElement::new();
+            text.content = String::from(line);
+            text.style = TextStyle::BOLD;
+            text.c_pair = c_pair;
+            self.notification_layer
+                .push(Box::new(text), Coord::new(0, i as i32));
+        }
+    }
+
+    fn update_confirm_layer(&mut self) {
+        self.confirm_layer = Layer::new();
+
+        let mut separator: Text = UiElement::new();
+        let mut prompt: Text = UiElement::new();
+        let mut answers: Text = UiElement::new();
+
+        separator.content = "=".repeat(self.win.get_size().x as usize);
+        separator.c_pair = COLOR_PAIR_SEP;
+        prompt.content = match &self.pending {
+            Some(pending) => pending.prompt(),
+            None => return,
+        };
+        prompt.style = TextStyle::BOLD;
+        answers.content = String::from("(y or n)");
+        answers.c_pair = COLOR_PAIR_UNTRACKED;
+
+        let s1 = prompt.size();
+        self.confirm_layer
+            .push(Box::new(separator), Coord::new(0, 0));
+        self.confirm_layer.push(Box::new(prompt), Coord::new(0, 2));
+        self.confirm_layer
+            .push(Box::new(answers), Coord::new(s1.x + 1, 2));
+    }
+
+    fn update_input_layer(&mut self) {
+        self.input_layer = Layer::new();
+
+        let prompt_text = match &self.input_purpose {
+            Some(InputPurpose::PullFrom) => String::from("Pull from (remote [branch]): "),
+            Some(InputPurpose::FetchFrom) => String::from("Fetch from remote: "),
+            Some(InputPurpose::PushTo) => String::from("Push to (remote [refspec...]): "),
+            Some(InputPurpose::CreateBranch(start)) => {
+                format!("Create branch starting at {}: ", start)
+            }
+            Some(InputPurpose::RenameBranch(old)) => format!("Rename {} to: ", old),
+            Some(InputPurpose::StashMessage) => String::from("Stash message (optional): "),
+            Some(InputPurpose::ResetTo) => String::from("Reset to revision: "),
+            Some(InputPurpose::Mainline(_)) => String::from("Parent number of merges: "),
+            None => return,
+        };
+        let remotes_hint = matches!(
+            self.input_purpose,
+            Some(InputPurpose::PullFrom | InputPurpose::FetchFrom | InputPurpose::PushTo)
+        );
+
+        let mut separator: Text = UiElement::new();
+        let mut prompt: Text = UiElement::new();
+        let mut input: Text = UiElement::new();
+        let mut hint: Text = UiElement::new();
+
+        separator.content = "=".repeat(self.win.get_size().x as usize);
+        separator.c_pair = COLOR_PAIR_SEP;
+        prompt.content = prompt_text;
+        prompt.c_pair = COLOR_PAIR_H3;
+        input.content = self.input_mode.input.clone();
+        input.c_pair = COLOR_PAIR_H1;
+        if remotes_hint {
+            let remotes = self.report(self.git.remotes()).unwrap_or_default();
+            hint.content = format!("Remotes: {}", remotes.join(", "));
+        }
+
+        let s1 = prompt.size();
+        self.input_layer.push(Box::new(separator), Coord::new(0, 0));
+        self.input_layer.push(Box::new(prompt), Coord::new(0, 2));
+        self.input_layer.push(Box::new(input), Coord::new(s1.x, 2));
+        self.input_layer.push(Box::new(hint), Coord::new(0, 3));
     }
 }
@@ -0,0 +1,248 @@
+use crate::util::char_width;
+
+/// Multi-line text being edited, with a cursor. Columns count chars, not
+/// bytes.
+pub struct TextBuffer {
+    lines: Vec<String>,
+    row: usize,
+    col: usize,
+}
+
+impl TextBuffer {
+    pub fn new() -> TextBuffer {
+        TextBuffer {
+            lines: vec![String::new()],
+            row: 0,
+            col: 0,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Row and column of the cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    pub fn clear(&mut self) {
+        *self = TextBuffer::new();
+    }
+
+    /// Replaces the text, the cursor goes to its start
+    pub fn set_text(&mut self, text: &str) {
+        self.lines = text.split('\n').map(String::from).collect();
+        self.row = 0;
+        self.col = 0;
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.col);
+        self.lines[self.row].insert(i, c);
+        self.col += 1;
+    }
+
+    /// Splits the line at the cursor
+    pub fn newline(&mut self) {
+        let i = self.byte_index(self.col);
+        let rest = self.lines[self.row].split_off(i);
+        self.row += 1;
+        self.col = 0;
+        self.lines.insert(self.row, rest);
+    }
+
+    /// Deletes the character before the cursor, joining the line to the
+    /// one above at its start
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let i = self.byte_index(self.col);
+            self.lines[self.row].remove(i);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    /// Deletes the character under the cursor, joining the next line at
+    /// the end of this one
+    pub fn delete(&mut self) {
+        if self.col < self.line_len(self.row) {
+            let i = self.byte_index(self.col);
+            self.lines[self.row].remove(i);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    /// Moves one character back, over any combining characters drawn on
+    /// top of it
+    pub fn left(&mut self) {
+        while let Some((row, col)) = self.prev_pos(self.row, self.col) {
+            self.row = row;
+            self.col = col;
+            if !self.on_combining() {
+                break;
+            }
+        }
+    }
+
+    pub fn right(&mut self) {
+        while let Some((row, col)) = self.next_pos(self.row, self.col) {
+            self.row = row;
+            self.col = col;
+            if !self.on_combining() {
+                break;
+            }
+        }
+    }
+
+    pub fn up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn line_start(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn line_end(&mut self) {
+        self.col = self.line_len(self.row);
+    }
+
+    /// Moves to the end of the next word, like `M-f` in Emacs
+    pub fn word_forward(&mut self) {
+        let mut pos = (self.row, self.col);
+        let mut in_word = false;
+        while let Some(c) = self.char_at(pos) {
+            if is_word_char(c) {
+                in_word = true;
+            } else if in_word {
+                break;
+            }
+            pos = match self.next_pos(pos.0, pos.1) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        self.row = pos.0;
+        self.col = pos.1;
+    }
+
+    /// Moves to the start of the previous word, like `M-b` in Emacs
+    pub fn word_backward(&mut self) {
+        let mut pos = (self.row, self.col);
+        let mut in_word = false;
+        while let Some(prev) = self.prev_pos(pos.0, pos.1) {
+            let c = self.char_at(prev).unwrap_or('\n');
+            if is_word_char(c) {
+                in_word = true;
+            } else if in_word {
+                break;
+            }
+            pos = prev;
+        }
+        self.row = pos.0;
+        self.col = pos.1;
+    }
+
+    /// Whether the cursor is on a character that takes up no columns of
+    /// its own
+    fn on_combining(&self) -> bool {
+        match self.char_at((self.row, self.col)) {
+            Some('\n') | None => false,
+            Some(c) => char_width(c) == 0,
+        }
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    /// Byte index of column `col` of the cursor's line
+    fn byte_index(&self, col: usize) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+    }
+
+    /// The character at `pos`, a newline past the end of every line but the
+    /// last
+    fn char_at(&self, (row, col): (usize, usize)) -> Option<char> {
+        match self.lines[row].chars().nth(col) {
+            Some(c) => Some(c),
+            None if row + 1 < self.lines.len() => Some('\n'),
+            None => None,
+        }
+    }
+
+    fn next_pos(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        if col < self.line_len(row) {
+            Some((row, col + 1))
+        } else if row + 1 < self.lines.len() {
+            Some((row + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    fn prev_pos(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        if col > 0 {
+            Some((row, col - 1))
+        } else if row > 0 {
+            Some((row - 1, self.line_len(row - 1)))
+        } else {
+            None
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The columns the rows of `line` start at when it's wrapped to `width`
+/// screen columns. Lines are broken after the last space that fits, words
+/// longer than a row are split.
+pub fn wrap(line: &str, width: usize) -> Vec<usize> {
+    let width = width.max(1);
+    let chars: Vec<char> = line.chars().collect();
+    let mut starts = vec![0];
+    let mut start = 0;
+    loop {
+        // Characters of the row that fit, the first one always does
+        let mut used = 0;
+        let mut end = start;
+        while end < chars.len() && (end == start || used + char_width(chars[end]) <= width) {
+            used += char_width(chars[end]);
+            end += 1;
+        }
+        if end == chars.len() {
+            return starts;
+        }
+
+        let row = &chars[start..end];
+        let end = match row.iter().rposition(|c| *c == ' ') {
+            Some(space) if space > 0 => start + space + 1,
+            _ => end,
+        };
+        starts.push(end);
+        start = end;
+    }
+}
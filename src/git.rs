@@ -1,136 +1,818 @@
-use std::path::PathBuf;
-use std::process::Command;
+mod branch;
+mod diff;
+mod job;
+mod log;
+mod progress;
+mod rebase;
+mod remote;
+mod sequencer;
+mod stash;
+mod status;
+
+pub use branch::*;
+pub use diff::*;
+pub use job::*;
+pub use log::*;
+pub use progress::*;
+pub use rebase::*;
+pub use remote::*;
+pub use sequencer::*;
+pub use stash::*;
+pub use status::*;
+
+use crate::util::path_from_bytes;
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 
 pub struct Git {
     work_dir: PathBuf,
 }
 
-impl Git {
-    pub fn new(path: PathBuf) -> Git {
-        Git { work_dir: path }
+/// What `Git::apply` should modify
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApplyTo {
+    Index,
+    WorkTree,
+    Both,
+}
+
+/// A git invocation that couldn't be started or exited unsuccessfully.
+#[derive(Debug)]
+pub struct GitError {
+    /// The command line, without the `-C <work dir>` prefix
+    pub command: String,
+    /// `None` if git couldn't be run at all, was killed by a signal or said
+    /// something that couldn't be parsed
+    pub status: Option<i32>,
+    pub stderr: String,
+}
+
+pub type GitResult<T> = Result<T, GitError>;
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.status {
+            Some(code) => write!(f, "`{}` failed with exit code {}", self.command, code)?,
+            None => write!(f, "`{}` failed", self.command)?,
+        }
+        if !self.stderr.is_empty() {
+            write!(f, ": {}", self.stderr)?;
+        }
+        Ok(())
     }
+}
 
-    pub fn untracked(&self) -> Vec<PathBuf> {
-        let child = Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
-            .arg("ls-files")
-            .arg("--others")
-            .arg("--exclude-standard")
-            .output()
-            .expect("Git failed");
+impl Error for GitError {}
 
-        (&String::from_utf8_lossy(&child.stdout))
-            .lines()
-            .map(|l| PathBuf::from(l))
-            .collect()
+impl GitError {
+    /// Whether `git branch -d` refused to delete an unmerged branch
+    pub fn is_unmerged_branch(&self) -> bool {
+        self.stderr.contains("is not fully merged")
     }
 
-    pub fn staged(&self) -> Vec<PathBuf> {
-        let child = Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
-            .arg("status")
-            .arg("--porcelain")
-            .output()
-            .expect("Couldn't run git status");
+    /// Whether the remote wanted credentials git or ssh weren't allowed to
+    /// prompt for, see `Git::batch`
+    pub fn is_auth_failure(&self) -> bool {
+        self.stderr.contains("terminal prompts disabled")
+            || self.stderr.contains("Permission denied (")
+            || self.stderr.contains("Host key verification failed")
+    }
 
-        (String::from_utf8_lossy(&child.stdout))
+    /// Whether `git commit` gave up because the message was left empty
+    pub fn is_empty_commit_message(&self) -> bool {
+        self.stderr
+            .contains("Aborting commit due to empty commit message")
+    }
+
+    /// The remote and branch git suggests pushing to when `git push` fails
+    /// because the current branch has no upstream.
+    pub fn suggested_upstream(&self) -> Option<(String, String)> {
+        if !self.stderr.contains("has no upstream branch") {
+            return None;
+        }
+        let line = self
+            .stderr
             .lines()
-            .filter(|l| l.chars().nth(1).unwrap() == ' ')
-            .map(|p| PathBuf::from(&p[3..]))
-            .collect()
+            .find_map(|l| l.trim().strip_prefix("git push --set-upstream "))?;
+        let (remote, branch) = line.split_once(' ')?;
+        Some((String::from(remote), String::from(branch)))
+    }
+}
+
+/// `s` quoted for `sh`
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Runs `cmd`, turning both spawn failures and non-zero exit codes into a
+/// `GitError`.
+fn run(cmd: &mut Command) -> GitResult<Output> {
+    run_with_input(cmd, None)
+}
+
+/// The command line of `cmd` as shown in errors, without the `-C <work dir>`
+/// prefix.
+fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args().skip(2))
+        .map(|a| a.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Like `run`, but feeds `input` to the command's stdin.
+fn run_with_input(cmd: &mut Command, input: Option<&[u8]>) -> GitResult<Output> {
+    let command = command_line(cmd);
+
+    let io_error = |e: io::Error| GitError {
+        command: command.clone(),
+        status: None,
+        stderr: e.to_string(),
+    };
+    let output = match input {
+        None => cmd.output().map_err(io_error)?,
+        Some(input) => {
+            let mut child = cmd
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(io_error)?;
+            // Dropping stdin afterwards closes it so git sees the end of input
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(input).map_err(io_error)?;
+            }
+            child.wait_with_output().map_err(io_error)?
+        }
+    };
+
+    check_output(command, output)
+}
+
+/// Turns a non-zero exit code of `command` into a `GitError`.
+fn check_output(command: String, output: Output) -> GitResult<Output> {
+    if !output.status.success() {
+        // Some commands, like commit, explain themselves on stdout instead
+        let msg = if output.stderr.is_empty() {
+            &output.stdout
+        } else {
+            &output.stderr
+        };
+        return Err(GitError {
+            command,
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(msg).trim_end().to_string(),
+        });
     }
 
-    pub fn unstaged(&self) -> Vec<PathBuf> {
-        let child = Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
+    Ok(output)
+}
+
+impl Git {
+    pub fn new(path: PathBuf) -> Git {
+        Git { work_dir: path }
+    }
+
+    /// A `git` command operating on the work dir. `run` relies on the
+    /// first two arguments being the `-C` option. Messages are kept
+    /// untranslated since some of them are parsed.
+    fn git(&self) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.arg("-C").arg(&self.work_dir).env("LC_ALL", "C");
+        cmd
+    }
+
+    /// A `git` command for a job that runs while the UI owns the terminal.
+    /// Neither git nor ssh may prompt for credentials or host keys on it,
+    /// they fail instead, unless the user configured that themselves.
+    fn batch(&self) -> Command {
+        let mut cmd = self.git();
+        if env::var_os("GIT_TERMINAL_PROMPT").is_none() {
+            cmd.env("GIT_TERMINAL_PROMPT", "0");
+        }
+        if env::var_os("GIT_SSH_COMMAND").is_none() && env::var_os("GIT_SSH").is_none() {
+            // The variable takes precedence over core.sshCommand, keep that
+            let ssh = run(self.git().arg("config").arg("core.sshCommand"))
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                .unwrap_or_else(|_| String::from("ssh"));
+            cmd.env("GIT_SSH_COMMAND", format!("{} -o BatchMode=yes", ssh));
+        }
+        cmd
+    }
+
+    pub fn status(&self) -> GitResult<RepoStatus> {
+        let output = run(self
+            .git()
             .arg("status")
-            .arg("--porcelain")
-            .output()
-            .expect("Couldn't run git status");
+            .arg("--porcelain=v2")
+            .arg("-z")
+            .arg("--branch"))?;
 
-        (String::from_utf8_lossy(&child.stdout))
-            .lines()
-            .filter(|l| l.chars().nth(0).unwrap() == ' ')
-            .map(|p| PathBuf::from(&p[3..]))
-            .collect()
+        Ok(RepoStatus::parse(&output.stdout))
     }
 
-    pub fn last_commit_msg(&self) -> String {
-        let last_commit = Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
+    pub fn last_commit_msg(&self) -> GitResult<String> {
+        let output = run(self
+            .git()
             .arg("--no-pager")
             .arg("log")
             .arg("-1")
-            .arg("--oneline")
-            .arg("--pretty=%B")
-            .output()
-            .expect("Couldn't get last commit");
-        String::from_utf8(last_commit.stdout).unwrap()
-    }
-
-    pub fn branch_name(&self) -> String {
-        let branch_name = Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
-            .arg("branch")
-            .arg("--show-current")
-            .output()
-            .expect("Couldn't get branch name");
-        String::from_utf8(branch_name.stdout).unwrap()
-    }
-
-    pub fn stage_file(&self, path: &PathBuf) {
-        Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
-            .arg("add")
-            .arg(path.to_str().unwrap())
-            .output()
-            .expect("Couldn't stage file");
-    }
-
-    pub fn unstage_file(&self, path: &PathBuf) {
-        Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
-            .arg("reset")
+            .arg("--pretty=%s"))?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    }
+
+    /// The diff of `path` between the index and either HEAD (`staged`) or
+    /// the work tree.
+    pub fn diff(&self, path: &Path, staged: bool) -> GitResult<FileDiff> {
+        let mut cmd = self.git();
+        cmd.arg("--no-pager")
+            .arg("diff")
+            .arg("--no-color")
+            .arg("--no-ext-diff");
+        if staged {
+            cmd.arg("--cached");
+        }
+        let output = run(cmd.arg("--").arg(path))?;
+
+        Ok(FileDiff::parse(&output.stdout)
+            .into_iter()
+            .next()
+            .unwrap_or_default())
+    }
+
+    pub fn stage_file(&self, path: &Path) -> GitResult<()> {
+        run(self.git().arg("add").arg("--").arg(path))?;
+        Ok(())
+    }
+
+    /// Applies `patch` to `target`, or takes it back out if `reverse` is set.
+    pub fn apply(&self, patch: &[u8], target: ApplyTo, reverse: bool) -> GitResult<()> {
+        let mut cmd = self.git();
+        cmd.arg("apply");
+        match target {
+            ApplyTo::Index => cmd.arg("--cached"),
+            ApplyTo::WorkTree => &mut cmd,
+            ApplyTo::Both => cmd.arg("--index"),
+        };
+        if reverse {
+            cmd.arg("--reverse");
+        }
+        run_with_input(cmd.arg("-"), Some(patch))?;
+        Ok(())
+    }
+
+    /// Throws away the work tree changes to `paths`, and the staged ones
+    /// too if `staged` is set.
+    pub fn restore(&self, paths: &[&Path], staged: bool) -> GitResult<()> {
+        let mut cmd = self.git();
+        cmd.arg("restore").arg("--worktree");
+        if staged {
+            cmd.arg("--staged");
+        }
+        run(cmd.arg("--").args(paths))?;
+        Ok(())
+    }
+
+    /// Deletes an untracked file or directory.
+    pub fn clean(&self, path: &Path) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("clean")
+            .arg("--force")
+            .arg("-d")
+            .arg("--")
+            .arg(path))?;
+        Ok(())
+    }
+
+    /// Unstages every path in `paths`, both sides of a rename have to be
+    /// reset together for it to disappear from the index.
+    pub fn unstage_file(&self, paths: &[&Path]) -> GitResult<()> {
+        run(self.git().arg("reset").arg("--").args(paths))?;
+        Ok(())
+    }
+
+    pub fn commit(&self, args: Vec<String>, msg: String) -> GitResult<()> {
+        run(self.git().arg("commit").arg("-m").arg(msg).args(args))?;
+        Ok(())
+    }
+
+    /// Replaces the checked out commit with one that also has the staged
+    /// changes and `msg` as its message
+    pub fn amend(&self, args: Vec<String>, msg: String) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("commit")
+            .arg("--amend")
+            .arg("-m")
+            .arg(msg)
+            .args(args))?;
+        Ok(())
+    }
+
+    /// Adds the staged changes to the checked out commit, keeping its
+    /// message
+    pub fn extend(&self, args: Vec<String>) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("commit")
+            .arg("--amend")
+            .arg("--no-edit")
+            .args(args))?;
+        Ok(())
+    }
+
+    /// Changes the message of the checked out commit and nothing else,
+    /// staged changes stay staged. `--all` is left out of `args`, it would
+    /// take the changes in.
+    pub fn reword(&self, args: Vec<String>, msg: String) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("commit")
+            .arg("--amend")
+            .arg("--only")
+            .arg("-m")
+            .arg(msg)
+            .args(args.into_iter().filter(|a| a != "--all")))?;
+        Ok(())
+    }
+
+    /// Commits the staged changes as a `fixup!` of `oid`, to be folded into
+    /// it by `git rebase --autosquash`. Its message is dropped then.
+    pub fn fixup(&self, args: Vec<String>, oid: &str) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("commit")
+            .arg(format!("--fixup={}", oid))
+            .args(args))?;
+        Ok(())
+    }
+
+    /// Like `fixup`, but the message of the `squash!` commit is kept
+    /// alongside the one of `oid` when they are folded together.
+    pub fn squash(&self, args: Vec<String>, oid: &str) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("commit")
+            .arg(format!("--squash={}", oid))
+            .arg("--no-edit")
+            .args(args))?;
+        Ok(())
+    }
+
+    /// The full message of the checked out commit
+    pub fn head_message(&self) -> GitResult<String> {
+        let output = run(self.git().arg("log").arg("-1").arg("--format=%B"))?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    }
+
+    /// Up to `count` commits of `branch`, or the current branch if `None`,
+    /// newest first and leaving out the first `skip`. Without a `count` it's
+    /// every commit after those. With `graph` the lines git draws between
+    /// them are included.
+    pub fn log(
+        &self,
+        count: Option<usize>,
+        skip: usize,
+        graph: bool,
+        branch: Option<&str>,
+    ) -> GitResult<Vec<LogLine>> {
+        let mut cmd = self.git();
+        cmd.arg("log")
+            .arg(format!("--format={}", COMMIT_FORMAT))
+            .arg("--decorate=full")
+            .arg("--date=short")
+            .arg(format!("--skip={}", skip));
+        if let Some(count) = count {
+            cmd.arg("-n").arg(count.to_string());
+        }
+        if graph {
+            cmd.arg("--graph");
+        }
+        cmd.args(branch).arg("--");
+        let output = run(&mut cmd)?;
+        Ok(LogLine::parse_list(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    /// The message and changes of `oid`. Merges show what they changed
+    /// relative to their first parent.
+    pub fn show(&self, oid: &str) -> GitResult<CommitDetails> {
+        let mut cmd = self.git();
+        cmd.arg("--no-pager")
+            .arg("show")
+            .arg("--no-color")
+            .arg("--no-ext-diff")
+            .arg("--patch")
+            .arg("-m")
+            .arg("--first-parent")
+            .arg(format!("--format={}", DETAILS_FORMAT))
+            .arg(oid);
+        let output = run(&mut cmd)?;
+        CommitDetails::parse(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| GitError {
+            command: command_line(&cmd),
+            status: None,
+            stderr: String::from("unexpected output"),
+        })
+    }
+
+    /// The user's editor as a `GIT_EDITOR` value. git picks it from
+    /// `GIT_EDITOR`, `core.editor`, `VISUAL` and `EDITOR`, in that order.
+    fn editor(&self) -> GitResult<String> {
+        let output = run(self.git().arg("var").arg("GIT_EDITOR"))?;
+        let editor = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        // git runs with the C locale so its messages can be parsed, the
+        // editor should get the user's
+        let locale = match env::var("LC_ALL") {
+            Ok(lc_all) => format!("LC_ALL={}; export LC_ALL; ", shell_quote(&lc_all)),
+            Err(_) => String::from("unset LC_ALL; "),
+        };
+        Ok(locale + &editor)
+    }
+
+    /// Commits with a message written in the user's editor, which takes
+    /// over the terminal until it exits.
+    pub fn commit_in_editor(&self, args: Vec<String>) -> GitResult<()> {
+        run(self
+            .git()
+            .env("GIT_EDITOR", self.editor()?)
+            .arg("commit")
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit()))?;
+        Ok(())
+    }
+
+    /// Local branches followed by remote ones
+    pub fn branches(&self) -> GitResult<Vec<Branch>> {
+        let output = run(self
+            .git()
+            .arg("for-each-ref")
+            .arg(format!("--format={}", BRANCH_FORMAT))
+            .arg("refs/heads")
+            .arg("refs/remotes"))?;
+        Ok(Branch::parse_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    pub fn checkout(&self, branch: &str) -> GitResult<()> {
+        run(self.git().arg("checkout").arg(branch))?;
+        Ok(())
+    }
+
+    /// Creates a local branch tracking the remote branch `remote_branch`
+    /// and checks it out.
+    pub fn checkout_tracking(&self, remote_branch: &str) -> GitResult<()> {
+        run(self.git().arg("checkout").arg("--track").arg(remote_branch))?;
+        Ok(())
+    }
+
+    /// Creates `branch` at `start` and checks it out.
+    pub fn create_branch(&self, branch: &str, start: &str) -> GitResult<()> {
+        run(self.git().arg("checkout").arg("-b").arg(branch).arg(start))?;
+        Ok(())
+    }
+
+    pub fn rename_branch(&self, old: &str, new: &str) -> GitResult<()> {
+        run(self.git().arg("branch").arg("-m").arg(old).arg(new))?;
+        Ok(())
+    }
+
+    /// Deletes a local branch. Unless `force` is set git refuses to delete
+    /// branches that aren't merged, see `GitError::is_unmerged_branch`.
+    pub fn delete_branch(&self, branch: &str, force: bool) -> GitResult<()> {
+        let flag = if force { "-D" } else { "-d" };
+        run(self.git().arg("branch").arg(flag).arg(branch))?;
+        Ok(())
+    }
+
+    /// The operation that stopped in the middle, if any
+    pub fn operation(&self) -> GitResult<Option<Operation>> {
+        Ok(Operation::detect(&self.git_dir()?))
+    }
+
+    fn git_dir(&self) -> GitResult<PathBuf> {
+        let output = run(self.git().arg("rev-parse").arg("--absolute-git-dir"))?;
+        let git_dir = output.stdout.strip_suffix(b"\n").unwrap_or(&output.stdout);
+        Ok(path_from_bytes(git_dir))
+    }
+
+    /// The step a stopped rebase is at and how many there are
+    pub fn rebase_step(&self) -> GitResult<Option<(usize, usize)>> {
+        let git_dir = self.git_dir()?;
+        let read = |dir: &str, file: &str| -> Option<usize> {
+            fs::read_to_string(git_dir.join(dir).join(file))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        Ok(
+            match (read("rebase-merge", "msgnum"), read("rebase-merge", "end")) {
+                (Some(step), Some(end)) => Some((step, end)),
+                _ => read("rebase-apply", "next").zip(read("rebase-apply", "last")),
+            },
+        )
+    }
+
+    /// The commit before `oid`, `None` for a root commit
+    pub fn parent(&self, oid: &str) -> GitResult<Option<String>> {
+        self.short_oid(&format!("{}^", oid))
+    }
+
+    /// The abbreviated hash of the commit `rev` names, `None` if it names
+    /// none
+    pub fn short_oid(&self, rev: &str) -> GitResult<Option<String>> {
+        let result = run(self
+            .git()
+            .arg("rev-parse")
+            .arg("--verify")
+            .arg("--quiet")
+            .arg("--short")
+            .arg(format!("{}^{{commit}}", rev)));
+        match result {
+            Ok(output) => Ok(Some(
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            )),
+            // With --quiet a missing commit is only told by the exit code
+            Err(e) if e.status == Some(1) && e.stderr.is_empty() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The todo list `git rebase -i` starts with for the commits after
+    /// `base`, or every commit without one. Like git it leaves out merges
+    /// and commits `base` already has.
+    pub fn rebase_todo(&self, base: Option<&str>) -> GitResult<Vec<TodoLine>> {
+        let range = match base {
+            Some(base) => format!("{}...HEAD", base),
+            None => String::from("HEAD"),
+        };
+        let output = run(self
+            .git()
+            .arg("log")
+            .args(TODO_COMMITS)
+            .arg(format!("--format={}", TODO_FORMAT))
+            .arg(range))?;
+        Ok(TodoLine::parse_list(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    /// Rebases the commits after `base`, or every commit without one, as
+    /// `todo` says. git takes the list through `GIT_SEQUENCE_EDITOR`, which
+    /// rewrites the one git wrote with it, see `TODO_EDITOR`. Rewording and
+    /// squashing run the user's editor, which takes over the terminal.
+    pub fn rebase_interactive(&self, base: Option<&str>, todo: &[TodoLine]) -> GitResult<()> {
+        let path = self.git_dir()?.join("VRGIT_REBASE_TODO");
+        fs::write(&path, TodoLine::format_list(todo)).map_err(|e| GitError {
+            command: format!("write {}", path.display()),
+            status: None,
+            stderr: e.to_string(),
+        })?;
+
+        let mut cmd = self.git();
+        cmd.env(
+            "GIT_SEQUENCE_EDITOR",
+            format!(
+                "sh -c {} vrgit-rebase {}",
+                shell_quote(TODO_EDITOR),
+                shell_quote(&path.to_string_lossy())
+            ),
+        )
+        .env("GIT_EDITOR", self.editor()?)
+        .arg("rebase")
+        .arg("-i");
+        match base {
+            Some(base) => cmd.arg(base),
+            None => cmd.arg("--root"),
+        };
+        let result = run(cmd.stdin(Stdio::inherit()).stdout(Stdio::inherit()));
+        // Only needed until git read it
+        let _ = fs::remove_file(&path);
+        result?;
+        Ok(())
+    }
+
+    /// Continues `op` once its conflicts are resolved. Commits it makes
+    /// get the message git prepared without asking for changes.
+    pub fn continue_operation(&self, op: Operation) -> GitResult<()> {
+        run(self
+            .git()
+            .env("GIT_EDITOR", "true")
+            .arg(op.command())
+            .arg("--continue"))?;
+        Ok(())
+    }
+
+    /// Leaves out the commit `op` stopped on and goes on with the next
+    pub fn skip_operation(&self, op: Operation) -> GitResult<()> {
+        run(self
+            .git()
+            .env("GIT_EDITOR", "true")
+            .arg(op.command())
+            .arg("--skip"))?;
+        Ok(())
+    }
+
+    /// What a cherry-pick or revert of several commits still has to apply,
+    /// empty when none stopped
+    pub fn sequencer_todo(&self) -> GitResult<Vec<SequencerLine>> {
+        let path = self.git_dir()?.join("sequencer").join("todo");
+        let todo = fs::read_to_string(path).unwrap_or_default();
+        Ok(SequencerLine::parse_list(&todo))
+    }
+
+    /// Applies the changes of `oids` onto HEAD, in the order given
+    pub fn cherry_pick(&self, args: Vec<String>, oids: &[String]) -> GitResult<Sequenced> {
+        self.sequence(Operation::CherryPick, args, oids)
+    }
+
+    /// Commits the reverse of `oids`, in the order given
+    pub fn revert(&self, args: Vec<String>, oids: &[String]) -> GitResult<Sequenced> {
+        self.sequence(Operation::Revert, args, oids)
+    }
+
+    /// Runs `op`, a cherry-pick or revert, over `oids`. With `--edit` the
+    /// user's editor takes over the terminal for every commit message.
+    fn sequence(&self, op: Operation, args: Vec<String>, oids: &[String]) -> GitResult<Sequenced> {
+        let mut cmd = self.git();
+        if args.iter().any(|a| a == "--edit") {
+            cmd.env("GIT_EDITOR", self.editor()?)
+                .stdin(Stdio::inherit())
+                .stdout(Stdio::inherit());
+        }
+        match run(cmd.arg(op.command()).args(args).args(oids)) {
+            Ok(_) => Ok(Sequenced::Done),
+            // Anything worse than a conflict exits with 128
+            Err(e) if e.status == Some(1) && self.operation()? == Some(op) => {
+                Ok(Sequenced::Stopped)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stops `op` and goes back to where it started
+    pub fn abort_operation(&self, op: Operation) -> GitResult<()> {
+        run(self.git().arg(op.command()).arg("--abort"))?;
+        Ok(())
+    }
+
+    /// Resolves the conflict in `path` with the version of `side`, or by
+    /// removing the file if that side `deleted` it.
+    pub fn take_side(&self, path: &Path, side: Side, deleted: bool) -> GitResult<()> {
+        if deleted {
+            run(self.git().arg("rm").arg("--quiet").arg("--").arg(path))?;
+            return Ok(());
+        }
+        run(self
+            .git()
+            .arg("checkout")
+            .arg(side.flag())
             .arg("--")
-            .arg(path.to_str().unwrap())
-            .output()
-            .expect("Couldn't stage file");
-    }
-
-    pub fn commit(&self, args: Vec<String>, msg: String) {
-        Command::new("git")
-            .args(
-                [
-                    vec![
-                        String::from("-C"),
-                        self.work_dir.to_str().unwrap().to_string(),
-                        String::from("commit"),
-                        String::from("-m"),
-                        msg,
-                    ],
-                    args.clone(),
-                ]
-                .concat(),
-            )
-            .output()
-            .expect("Couldn't commit");
-    }
-
-    pub fn push(&self) -> String {
-        let output = Command::new("git")
-            .arg("-C")
-            .arg(self.work_dir.to_str().unwrap())
-            .arg("push")
-            .output()
-            .expect("Couldn't commit");
-        let err = String::from_utf8(output.stderr).unwrap();
-        err
+            .arg(path))?;
+        self.mark_resolved(path)
+    }
+
+    /// Marks the conflict in `path` resolved with whatever is in the work
+    /// tree, which includes the file being gone.
+    pub fn mark_resolved(&self, path: &Path) -> GitResult<()> {
+        run(self.git().arg("add").arg("--all").arg("--").arg(path))?;
+        Ok(())
+    }
+
+    /// Resolves the conflict in `path` with `git mergetool`, which takes
+    /// over the terminal until the tool exits.
+    pub fn mergetool(&self, path: &Path) -> GitResult<()> {
+        let mut cmd = self.git();
+        // The tool should get the user's locale, like the editor
+        match env::var("LC_ALL") {
+            Ok(lc_all) => cmd.env("LC_ALL", lc_all),
+            Err(_) => cmd.env_remove("LC_ALL"),
+        };
+        run(cmd
+            .arg("mergetool")
+            .arg("--no-prompt")
+            .arg("--")
+            .arg(path)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit()))?;
+        Ok(())
+    }
+
+    /// Points the current branch, or a detached HEAD, at `rev`. `args`
+    /// holds the mode, `--mixed` if it's empty.
+    pub fn reset(&self, args: Vec<String>, rev: &str) -> GitResult<()> {
+        run(self
+            .git()
+            .arg("reset")
+            .arg("--quiet")
+            .args(args)
+            .arg(rev)
+            .arg("--"))?;
+        Ok(())
+    }
+
+    /// Stashes, newest first
+    pub fn stashes(&self) -> GitResult<Vec<Stash>> {
+        let output = run(self
+            .git()
+            .arg("stash")
+            .arg("list")
+            .arg(format!("--format={}", STASH_FORMAT)))?;
+        Ok(Stash::parse_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Stashes the local changes, with `msg` as the message unless it's
+    /// empty. Returns false if there was nothing to stash.
+    pub fn stash(&self, args: Vec<String>, msg: &str) -> GitResult<bool> {
+        let mut cmd = self.git();
+        cmd.arg("stash").arg("push").args(args);
+        if !msg.is_empty() {
+            cmd.arg("-m").arg(msg);
+        }
+        let output = run(&mut cmd)?;
+        Ok(!String::from_utf8_lossy(&output.stdout).contains("No local changes to save"))
+    }
+
+    /// Applies `stash` to the work tree, dropping it afterwards if `pop`
+    /// is set and nothing conflicted.
+    pub fn stash_apply(&self, stash: &str, pop: bool) -> GitResult<StashApplied> {
+        let action = if pop { "pop" } else { "apply" };
+        let err = match run(self.git().arg("stash").arg(action).arg(stash)) {
+            Ok(_) => return Ok(StashApplied::Clean),
+            Err(err) => err,
+        };
+        // git refuses to apply over unmerged paths, any there are now come
+        // from the stash. The CONFLICT lines can't be relied on, they go
+        // to stdout.
+        if self.status()?.unmerged().next().is_some() {
+            Ok(StashApplied::Conflicts)
+        } else {
+            Err(err)
+        }
+    }
+
+    pub fn stash_drop(&self, stash: &str) -> GitResult<()> {
+        run(self.git().arg("stash").arg("drop").arg(stash))?;
+        Ok(())
+    }
+
+    pub fn remotes(&self) -> GitResult<Vec<String>> {
+        let output = run(self.git().arg("remote"))?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    /// Starts fetching from `remote`, or the current branch's remote if
+    /// `None`. The report is written to stderr, see `FetchResult::parse`.
+    pub fn fetch(&self, args: Vec<String>, remote: Option<&str>) -> GitResult<Job> {
+        Job::spawn(
+            self.batch()
+                .arg("fetch")
+                .arg("--progress")
+                .args(args)
+                .args(remote),
+        )
+    }
+
+    /// Starts pulling from `from`, a remote optionally followed by
+    /// branches, or the upstream of the current branch if it is empty. See
+    /// `PullResult::parse` for the output.
+    pub fn pull(&self, args: Vec<String>, from: &[&str]) -> GitResult<Job> {
+        Job::spawn(
+            self.batch()
+                .arg("pull")
+                .arg("--progress")
+                .args(args)
+                .args(from),
+        )
+    }
+
+    /// Starts pushing to `to`, a remote optionally followed by refspecs, or
+    /// where git pushes by default if it is empty. git reports on the push
+    /// on stderr.
+    pub fn push(&self, args: Vec<String>, to: &[&str]) -> GitResult<Job> {
+        Job::spawn(
+            self.batch()
+                .arg("push")
+                .arg("--progress")
+                .args(args)
+                .args(to),
+        )
     }
 }
@@ -0,0 +1,61 @@
+/// Format passed to `git for-each-ref`, fields are separated by NUL bytes
+pub const BRANCH_FORMAT: &str =
+    "%(HEAD)%00%(refname)%00%(objectname:short)%00%(upstream:short)%00%(subject)";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch {
+    /// `main` for local branches, `origin/main` for remote ones
+    pub name: String,
+    pub remote: bool,
+    /// Whether this is the checked out branch
+    pub head: bool,
+    pub oid: String,
+    pub upstream: Option<String>,
+    /// Subject of the commit the branch points to
+    pub subject: String,
+}
+
+impl Branch {
+    /// Parses the output of `git for-each-ref` run with `BRANCH_FORMAT`.
+    /// Symbolic refs like `origin/HEAD` are left out, they aren't branches
+    /// one would want to act on.
+    pub fn parse_list(output: &str) -> Vec<Branch> {
+        output.lines().filter_map(Branch::parse).collect()
+    }
+
+    fn parse(line: &str) -> Option<Branch> {
+        let fields: Vec<&str> = line.splitn(5, '\0').collect();
+        if fields.len() != 5 {
+            return None;
+        }
+
+        let (name, remote) = if let Some(name) = fields[1].strip_prefix("refs/heads/") {
+            (name, false)
+        } else if let Some(name) = fields[1].strip_prefix("refs/remotes/") {
+            (name, true)
+        } else {
+            return None;
+        };
+        if remote && name.ends_with("/HEAD") {
+            return None;
+        }
+
+        Some(Branch {
+            name: String::from(name),
+            remote,
+            head: fields[0] == "*",
+            oid: String::from(fields[2]),
+            upstream: Some(fields[3]).filter(|u| !u.is_empty()).map(String::from),
+            subject: String::from(fields[4]),
+        })
+    }
+
+    /// The name a local branch tracking this remote branch would get
+    pub fn local_name(&self) -> &str {
+        if self.remote {
+            self.name.split_once('/').map_or(&self.name[..], |(_, n)| n)
+        } else {
+            &self.name
+        }
+    }
+}
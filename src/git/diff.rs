@@ -0,0 +1,249 @@
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+    /// `\ No newline at end of file`, refers to the line before it
+    NoNewline,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// The line without its `+`/`-`/` ` prefix
+    pub content: String,
+    /// The line as git printed it, without the prefix and the newline but
+    /// with the `\r` of a CRLF line ending and in the file's encoding.
+    /// Patches are built from it so they match the file byte for byte.
+    pub raw: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_count: u32,
+    pub new_start: u32,
+    pub new_count: u32,
+    /// Whatever git put after the closing `@@`, usually the enclosing function
+    pub context: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileDiff {
+    /// Everything from `diff --git` up to the first hunk
+    pub header: Vec<String>,
+    /// The header as git printed it, newlines included
+    pub raw_header: Vec<u8>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl LineKind {
+    pub fn prefix(self) -> char {
+        match self {
+            LineKind::Context => ' ',
+            LineKind::Added => '+',
+            LineKind::Removed => '-',
+            LineKind::NoNewline => '\\',
+        }
+    }
+}
+
+impl DiffLine {
+    pub fn prefix(&self) -> char {
+        self.kind.prefix()
+    }
+}
+
+impl Hunk {
+    /// Parses a `@@ -old_start,old_count +new_start,new_count @@ context` line.
+    fn parse_header(line: &str) -> Option<Hunk> {
+        let rest = line.strip_prefix("@@ -")?;
+        let end = rest.find(" @@")?;
+        let mut ranges = rest[..end].split(" +");
+        let (old_start, old_count) = parse_range(ranges.next()?)?;
+        let (new_start, new_count) = parse_range(ranges.next()?)?;
+
+        Some(Hunk {
+            old_start,
+            old_count,
+            new_start,
+            new_count,
+            context: rest[end + 3..].trim_start().to_string(),
+            lines: vec![],
+        })
+    }
+
+    pub fn header(&self) -> String {
+        let mut header = format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        );
+        if !self.context.is_empty() {
+            header.push(' ');
+            header += &self.context;
+        }
+        header
+    }
+}
+
+/// Parses `start[,count]`, git leaves out the count when it is 1.
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let mut parts = range.splitn(2, ',');
+    let start = parts.next()?.parse().ok()?;
+    let count = match parts.next() {
+        Some(c) => c.parse().ok()?,
+        None => 1,
+    };
+    Some((start, count))
+}
+
+impl FileDiff {
+    /// Parses the output of `git diff`, one `FileDiff` per `diff --git` section.
+    /// Lines are only split at `\n`, files with CRLF line endings keep
+    /// their `\r` in the raw lines.
+    pub fn parse(output: &[u8]) -> Vec<FileDiff> {
+        let mut files: Vec<FileDiff> = vec![];
+
+        for raw in output.split_inclusive(|&b| b == b'\n') {
+            let raw = raw.strip_suffix(b"\n").unwrap_or(raw);
+            let text = String::from_utf8_lossy(raw.strip_suffix(b"\r").unwrap_or(raw));
+            let line = text.as_ref();
+            if line.starts_with("diff ") || files.is_empty() {
+                files.push(FileDiff::default());
+            }
+            let file = files.last_mut().unwrap();
+
+            if line.starts_with("@@ ") {
+                if let Some(hunk) = Hunk::parse_header(line) {
+                    file.hunks.push(hunk);
+                    continue;
+                }
+            }
+
+            match file.hunks.last_mut() {
+                None => {
+                    file.header.push(String::from(line));
+                    file.raw_header.extend(raw);
+                    file.raw_header.push(b'\n');
+                }
+                Some(hunk) => {
+                    let mut chars = line.chars();
+                    let kind = match chars.next() {
+                        Some('+') => LineKind::Added,
+                        Some('-') => LineKind::Removed,
+                        Some('\\') => LineKind::NoNewline,
+                        _ => LineKind::Context,
+                    };
+                    hunk.lines.push(DiffLine {
+                        kind,
+                        content: String::from(chars.as_str()),
+                        raw: raw.get(1..).unwrap_or_default().to_vec(),
+                    });
+                }
+            }
+        }
+
+        files
+    }
+
+    /// The path the diff is for as git printed it, the new one if the
+    /// file was renamed
+    pub fn path(&self) -> Option<&str> {
+        let prefixed = |prefix: &str| self.header.iter().find_map(|l| l.strip_prefix(prefix));
+        prefixed("+++ b/")
+            .or_else(|| prefixed("--- a/"))
+            .or_else(|| {
+                let paths = self.header.first()?.strip_prefix("diff --git a/")?;
+                paths.rsplit_once(" b/").map(|(_, new)| new)
+            })
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.header.iter().any(|l| l.starts_with("Binary files "))
+    }
+
+    /// Builds a patch applying only hunk `hunk`, or only the lines of it in
+    /// `selection` if given. Unselected changes are turned into context or
+    /// dropped depending on whether they exist on the side the patch is
+    /// applied to, which for `reverse` patches is the new side.
+    ///
+    /// Returns `None` if the selection doesn't contain any changes.
+    pub fn hunk_patch(
+        &self,
+        hunk: usize,
+        selection: Option<RangeInclusive<usize>>,
+        reverse: bool,
+    ) -> Option<Vec<u8>> {
+        let hunk = self.hunks.get(hunk)?;
+        let selected = |i: usize| selection.as_ref().is_none_or(|s| s.contains(&i));
+        let patch_line = |kind: LineKind, raw: &[u8]| {
+            let mut line = vec![kind.prefix() as u8];
+            line.extend(raw);
+            line
+        };
+
+        let mut lines: Vec<Vec<u8>> = vec![];
+        let mut old_count = 0;
+        let mut new_count = 0;
+        let mut has_changes = false;
+        // Whether the line a `\ No newline` marker refers to made it in
+        let mut kept_previous = false;
+
+        for (i, line) in hunk.lines.iter().enumerate() {
+            let kind = match (line.kind, selected(i), reverse) {
+                (LineKind::NoNewline, _, _) => {
+                    if kept_previous {
+                        lines.push(patch_line(line.kind, &line.raw));
+                    }
+                    continue;
+                }
+                (LineKind::Context, _, _) => Some(LineKind::Context),
+                (kind, true, _) => {
+                    has_changes = true;
+                    Some(kind)
+                }
+                (LineKind::Added, false, false) | (LineKind::Removed, false, true) => None,
+                (LineKind::Added, false, true) | (LineKind::Removed, false, false) => {
+                    Some(LineKind::Context)
+                }
+            };
+
+            kept_previous = kind.is_some();
+            if let Some(kind) = kind {
+                match kind {
+                    LineKind::Added => new_count += 1,
+                    LineKind::Removed => old_count += 1,
+                    _ => {
+                        old_count += 1;
+                        new_count += 1;
+                    }
+                }
+                lines.push(patch_line(kind, &line.raw));
+            }
+        }
+
+        if !has_changes {
+            return None;
+        }
+
+        let header = Hunk {
+            old_count,
+            new_count,
+            lines: vec![],
+            context: hunk.context.clone(),
+            ..*hunk
+        };
+
+        let mut patch = self.raw_header.clone();
+        patch.extend(header.header().as_bytes());
+        patch.push(b'\n');
+        for line in lines {
+            patch.extend(line);
+            patch.push(b'\n');
+        }
+        Some(patch)
+    }
+}
@@ -0,0 +1,203 @@
+use super::{check_output, command_line, GitError, GitResult, Progress};
+
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a cancelled command gets to clean up, like removing the lock
+/// files it holds, before it is killed
+const CANCEL_GRACE: Duration = Duration::from_secs(3);
+/// How often a cancelled command is checked on during the grace period
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// Something a running job reports back.
+#[derive(Debug)]
+pub enum JobEvent {
+    /// A line of stderr that isn't a progress update
+    Line(String),
+    Progress(Progress),
+    Done(GitResult<Output>),
+}
+
+/// A git command running on a worker thread. Its stderr is streamed back
+/// line by line while it runs, followed by the result once it exits. The
+/// stderr of the result leaves out the progress updates.
+pub struct Job {
+    command: String,
+    child: Arc<Mutex<Child>>,
+    events: Receiver<JobEvent>,
+}
+
+impl Job {
+    pub fn spawn(cmd: &mut Command) -> GitResult<Job> {
+        let command = command_line(cmd);
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| GitError {
+                command: command.clone(),
+                status: None,
+                stderr: e.to_string(),
+            })?;
+
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let child = Arc::new(Mutex::new(child));
+        let (sender, events) = mpsc::channel();
+
+        let worker_child = Arc::clone(&child);
+        let worker_command = command.clone();
+        thread::spawn(move || {
+            // Read stdout on a thread of its own so neither pipe can fill up
+            // and block git while the other one is being read
+            let stdout_reader = thread::spawn(move || {
+                let mut buf = vec![];
+                if let Some(mut stdout) = stdout {
+                    let _ = stdout.read_to_end(&mut buf);
+                }
+                buf
+            });
+            let stderr = match stderr {
+                Some(stderr) => stream_lines(stderr, &sender),
+                None => vec![],
+            };
+            let stdout = stdout_reader.join().unwrap_or_default();
+
+            let status = worker_child.lock().unwrap().wait();
+            let result = match status {
+                Ok(status) => check_output(
+                    worker_command,
+                    Output {
+                        status,
+                        stdout,
+                        stderr,
+                    },
+                ),
+                Err(e) => Err(GitError {
+                    command: worker_command,
+                    status: None,
+                    stderr: e.to_string(),
+                }),
+            };
+            // Nobody is listening anymore if the job was cancelled
+            let _ = sender.send(JobEvent::Done(result));
+        });
+
+        Ok(Job {
+            command,
+            child,
+            events,
+        })
+    }
+
+    /// The next event, if there is one yet.
+    pub fn poll(&self) -> Option<JobEvent> {
+        match self.events.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(JobEvent::Done(Err(GitError {
+                command: self.command.clone(),
+                status: None,
+                stderr: String::from("the job stopped unexpectedly"),
+            }))),
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Asks the command to stop, killing it if it's still running after
+    /// `CANCEL_GRACE`. git removes its lock files when asked to stop, a
+    /// killed git leaves them behind for the user to delete. The job
+    /// shouldn't be polled afterwards, whatever it reports as it winds down
+    /// is of no interest.
+    pub fn cancel(&self) {
+        let child = Arc::clone(&self.child);
+        // Waiting for the command to go would freeze the UI
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut terminated = false;
+            while start.elapsed() < CANCEL_GRACE {
+                // The worker only holds the lock while reaping a command
+                // that has already closed its output
+                if let Ok(mut child) = child.try_lock() {
+                    match child.try_wait() {
+                        Ok(None) if !terminated => {
+                            terminate(&mut child);
+                            terminated = true;
+                        }
+                        Ok(None) => {}
+                        _ => return,
+                    }
+                }
+                thread::sleep(CANCEL_POLL);
+            }
+            if let Ok(mut child) = child.lock() {
+                let _ = child.kill();
+            }
+        });
+    }
+}
+
+/// Sends SIGTERM to `child`, which mustn't have been reaped yet or the pid
+/// could belong to another process
+#[cfg(unix)]
+fn terminate(child: &mut Child) {
+    // SAFETY: kill has no memory safety requirements
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+/// There is no asking a process to stop, it's killed right away
+#[cfg(not(unix))]
+fn terminate(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Sends every `\r` or `\n` terminated line of `stderr` to `sender`, as a
+/// progress update if it is one. Once the pipe is closed the lines that
+/// weren't progress updates are returned.
+fn stream_lines(mut stderr: impl Read, sender: &Sender<JobEvent>) -> Vec<u8> {
+    let mut kept = vec![];
+    let mut line = vec![];
+    let mut buf = [0; 4096];
+
+    let mut send = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line);
+        let event = match Progress::parse(&line) {
+            Some(progress) => JobEvent::Progress(progress),
+            None => {
+                kept.extend_from_slice(line.trim_end().as_bytes());
+                kept.push(b'\n');
+                JobEvent::Line(line.trim_end().to_string())
+            }
+        };
+        let _ = sender.send(event);
+    };
+
+    while let Ok(n) = stderr.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        for &byte in &buf[..n] {
+            if byte != b'\r' && byte != b'\n' {
+                line.push(byte);
+            } else if !line.is_empty() {
+                send(&line);
+                line.clear();
+            }
+        }
+    }
+    if !line.is_empty() {
+        send(&line);
+    }
+
+    kept
+}
@@ -0,0 +1,157 @@
+use super::FileDiff;
+
+/// Separates the fields of `COMMIT_FORMAT`. The first one also marks where
+/// the graph `git log --graph` draws in front of a commit ends.
+const FIELD_SEP: char = '\x1f';
+
+/// Format passed to `git log` together with `--decorate=full` and
+/// `--date=short`
+pub const COMMIT_FORMAT: &str = "%x1f%h%x1f%an%x1f%ad%x1f%D%x1f%s";
+
+/// Format passed to `git show`, the message ends at a NUL byte and the
+/// diff follows
+pub const DETAILS_FORMAT: &str = "%H%x1f%an <%ae>%x1f%ad%x1f%B%x00";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RefKind {
+    /// A detached HEAD, or the branch HEAD points to
+    Head,
+    Local,
+    Remote,
+    Tag,
+    /// Anything else, like `refs/stash`
+    Other,
+}
+
+/// A ref pointing to a commit, as `git log --decorate=full` lists them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ref {
+    pub kind: RefKind,
+    /// Without the `refs/heads/`, `refs/remotes/` or `refs/tags/` prefix
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Commit {
+    /// Abbreviated hash
+    pub oid: String,
+    pub author: String,
+    pub date: String,
+    pub refs: Vec<Ref>,
+    pub subject: String,
+}
+
+/// A line of `git log` output, a commit or just the graph connecting them
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    /// What `--graph` drew in front of the commit, empty without it
+    pub graph: String,
+    pub commit: Option<Commit>,
+}
+
+/// Everything `git show` says about a commit
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommitDetails {
+    /// Full hash
+    pub oid: String,
+    /// Name and email
+    pub author: String,
+    pub date: String,
+    pub message: String,
+    pub diffs: Vec<FileDiff>,
+}
+
+impl Ref {
+    /// Parses the decorations `%D` expands to with `--decorate=full`,
+    /// like `HEAD -> refs/heads/main, refs/remotes/origin/main, tag: refs/tags/v1`
+    pub fn parse_list(decorations: &str) -> Vec<Ref> {
+        let mut refs = vec![];
+        for decoration in decorations.split(", ").filter(|d| !d.is_empty()) {
+            let name = match decoration.strip_prefix("HEAD -> ") {
+                Some(branch) => {
+                    refs.push(Ref::new(RefKind::Head, "HEAD"));
+                    branch
+                }
+                None => decoration,
+            };
+            let name = name.strip_prefix("tag: ").unwrap_or(name);
+
+            refs.push(if name == "HEAD" {
+                Ref::new(RefKind::Head, name)
+            } else if let Some(branch) = name.strip_prefix("refs/heads/") {
+                Ref::new(RefKind::Local, branch)
+            } else if let Some(branch) = name.strip_prefix("refs/remotes/") {
+                Ref::new(RefKind::Remote, branch)
+            } else if let Some(tag) = name.strip_prefix("refs/tags/") {
+                Ref::new(RefKind::Tag, tag)
+            } else {
+                Ref::new(RefKind::Other, name)
+            });
+        }
+        refs
+    }
+
+    fn new(kind: RefKind, name: &str) -> Ref {
+        Ref {
+            kind,
+            name: String::from(name),
+        }
+    }
+}
+
+impl LogLine {
+    /// Parses the output of `git log` run with `COMMIT_FORMAT`, with or
+    /// without `--graph`
+    pub fn parse_list(output: &str) -> Vec<LogLine> {
+        output.lines().map(LogLine::parse).collect()
+    }
+
+    fn parse(line: &str) -> LogLine {
+        let (graph, fields) = match line.split_once(FIELD_SEP) {
+            Some((graph, fields)) => (graph, fields),
+            None => {
+                return LogLine {
+                    graph: String::from(line),
+                    commit: None,
+                }
+            }
+        };
+
+        let fields: Vec<&str> = fields.splitn(5, FIELD_SEP).collect();
+        let commit = match fields[..] {
+            [oid, author, date, refs, subject] => Some(Commit {
+                oid: String::from(oid),
+                author: String::from(author),
+                date: String::from(date),
+                refs: Ref::parse_list(refs),
+                subject: String::from(subject),
+            }),
+            _ => None,
+        };
+        LogLine {
+            graph: String::from(graph),
+            commit,
+        }
+    }
+}
+
+impl CommitDetails {
+    /// Parses the output of `git show --patch` run with `DETAILS_FORMAT`
+    pub fn parse(output: &str) -> Option<CommitDetails> {
+        let (header, diff) = output.split_once('\0')?;
+        let fields: Vec<&str> = header.splitn(4, FIELD_SEP).collect();
+        match fields[..] {
+            [oid, author, date, message] => Some(CommitDetails {
+                oid: String::from(oid),
+                author: String::from(author),
+                date: String::from(date),
+                message: String::from(message.trim_end()),
+                diffs: FileDiff::parse(diff.trim_start_matches('\n').as_bytes())
+                    .into_iter()
+                    .filter(|d| !d.header.is_empty())
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+/// An update git prints to stderr while it works when run with
+/// `--progress`, like `Writing objects:  45% (9/20), 1.2 MiB | 300 KiB/s`.
+/// Updates to the same phase are separated by `\r`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// `Counting objects`, `Writing objects` and so on
+    pub phase: String,
+    /// Whether the update came from the other end of the connection
+    pub remote: bool,
+    pub current: u64,
+    /// `None` for phases that don't know how much work there is, these
+    /// only count
+    pub total: Option<u64>,
+    /// Whether the phase is finished, git ends its last update with `done.`
+    pub done: bool,
+}
+
+impl Progress {
+    pub fn parse(line: &str) -> Option<Progress> {
+        let line = line.trim();
+        let (line, remote) = match line.strip_prefix("remote: ") {
+            Some(rest) => (rest.trim_start(), true),
+            None => (line, false),
+        };
+        let (phase, rest) = line.split_once(": ")?;
+        let rest = rest.trim_start();
+        let done = rest.ends_with("done.");
+
+        let (current, total) = match rest.split_once('%') {
+            // ` 45% (9/20)`, the percentage can be worked out from the counts
+            Some((_, counts)) => {
+                let counts = counts.trim_start().strip_prefix('(')?;
+                let (current, total) = counts.split(')').next()?.split_once('/')?;
+                (current.parse().ok()?, Some(total.parse().ok()?))
+            }
+            // `5, done.`
+            None => (rest.split(',').next()?.trim().parse().ok()?, None),
+        };
+
+        Some(Progress {
+            phase: String::from(phase),
+            remote,
+            current,
+            total,
+            done,
+        })
+    }
+
+    /// How far along the phase is, between 0 and 1, if that is known
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.current as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/// Format passed to `git log` when listing the commits to rebase, the
+/// fields are separated by a NUL byte
+pub const TODO_FORMAT: &str = "%h%x00%s";
+
+/// Arguments of `git log` listing the commits `git rebase -i` puts in its
+/// todo list, given `<base>...HEAD`
+pub const TODO_COMMITS: &[&str] = &[
+    "--reverse",
+    "--topo-order",
+    "--right-only",
+    "--cherry-pick",
+    "--no-merges",
+];
+
+/// Script run as `GIT_SEQUENCE_EDITOR` with our todo list in `$1` and the
+/// one git wrote in `$2`. The commits of git's list are put in our order
+/// with our verbs, lines of git's that match none of ours are kept after
+/// them.
+pub const TODO_EDITOR: &str = r#"awk '
+NR == FNR { n++; verb[n] = $1; oid[n] = $2; next }
+/^[a-z]/ { line[$2] = $0; order[++m] = $2 }
+END {
+    for (i = 1; i <= n; i++)
+        for (g in line)
+            if (index(g, oid[i]) == 1 || index(oid[i], g) == 1) {
+                sub(/^[a-z]+/, verb[i], line[g])
+                print line[g]
+                delete line[g]
+                break
+            }
+    for (j = 1; j <= m; j++)
+        if (order[j] in line)
+            print line[order[j]]
+}' "$1" "$2" > "$2.vrgit" && mv "$2.vrgit" "$2""#;
+
+/// What a line of the todo list of `git rebase -i` does with its commit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TodoVerb {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TodoLine {
+    pub verb: TodoVerb,
+    /// Abbreviated hash
+    pub oid: String,
+    pub subject: String,
+}
+
+impl TodoVerb {
+    /// The command as written in the todo list
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoVerb::Pick => "pick",
+            TodoVerb::Reword => "reword",
+            TodoVerb::Edit => "edit",
+            TodoVerb::Squash => "squash",
+            TodoVerb::Fixup => "fixup",
+            TodoVerb::Drop => "drop",
+        }
+    }
+
+    /// Whether it folds its commit into the one before, so it can't be
+    /// the first
+    pub fn melds(&self) -> bool {
+        matches!(self, TodoVerb::Squash | TodoVerb::Fixup)
+    }
+}
+
+impl TodoLine {
+    /// Parses the output of `git log` run with `TODO_FORMAT`, every commit
+    /// is picked.
+    pub fn parse_list(output: &str) -> Vec<TodoLine> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (oid, subject) = line.split_once('\0')?;
+                Some(TodoLine {
+                    verb: TodoVerb::Pick,
+                    oid: String::from(oid),
+                    subject: String::from(subject),
+                })
+            })
+            .collect()
+    }
+
+    /// The todo list git reads back, oldest commit first
+    pub fn format_list(lines: &[TodoLine]) -> String {
+        lines
+            .iter()
+            .map(|l| format!("{} {} {}\n", l.verb.name(), l.oid, l.subject))
+            .collect()
+    }
+}
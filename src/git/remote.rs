@@ -0,0 +1,222 @@
+use std::fmt;
+
+/// Ref updates listed individually in a fetch report, the rest are counted
+const MAX_LISTED_UPDATES: usize = 5;
+
+/// How a ref changed during a fetch, from the flag git prints before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RefUpdateKind {
+    FastForward,
+    Forced,
+    Pruned,
+    TagUpdate,
+    New,
+    Rejected,
+    UpToDate,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefUpdate {
+    pub kind: RefUpdateKind,
+    /// `a1b2c3d..e4f5a6b`, `[new branch]` and so on
+    pub summary: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchResult {
+    /// The url from the `From <url>` line
+    pub url: Option<String>,
+    pub updates: Vec<RefUpdate>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PullOutcome {
+    UpToDate,
+    FastForward,
+    Merged,
+    Rebased,
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PullResult {
+    pub fetch: FetchResult,
+    pub outcome: PullOutcome,
+    /// `old..new` for fast-forwards
+    pub range: Option<String>,
+    /// The `n files changed, ...` line
+    pub stat: Option<String>,
+}
+
+impl RefUpdateKind {
+    fn from_flag(flag: char) -> Option<RefUpdateKind> {
+        match flag {
+            ' ' => Some(RefUpdateKind::FastForward),
+            '+' => Some(RefUpdateKind::Forced),
+            '-' => Some(RefUpdateKind::Pruned),
+            't' => Some(RefUpdateKind::TagUpdate),
+            '*' => Some(RefUpdateKind::New),
+            '!' => Some(RefUpdateKind::Rejected),
+            '=' => Some(RefUpdateKind::UpToDate),
+            _ => None,
+        }
+    }
+}
+
+impl RefUpdate {
+    /// Parses a ` <flag> <summary> <from> -> <to> [(<reason>)]` line.
+    fn parse(line: &str) -> Option<RefUpdate> {
+        let mut chars = line.chars();
+        if chars.next()? != ' ' {
+            return None;
+        }
+        let kind = RefUpdateKind::from_flag(chars.next()?)?;
+        let rest = chars.as_str().trim_start();
+
+        // Bracketed summaries like `[new branch]` contain spaces
+        let (summary, rest) = if rest.starts_with('[') {
+            let end = rest.find(']')? + 1;
+            (&rest[..end], &rest[end..])
+        } else {
+            let end = rest.find(' ')?;
+            (&rest[..end], &rest[end..])
+        };
+
+        let mut refs = rest.splitn(2, " -> ");
+        let from = refs.next()?.trim();
+        let to = refs.next()?.split(" (").next()?.trim();
+
+        Some(RefUpdate {
+            kind,
+            summary: String::from(summary),
+            from: String::from(from),
+            to: String::from(to),
+        })
+    }
+}
+
+impl FetchResult {
+    /// Parses the report git fetch writes to stderr.
+    pub fn parse(output: &str) -> FetchResult {
+        let mut result = FetchResult::default();
+        for line in output.lines() {
+            if let Some(url) = line.strip_prefix("From ") {
+                result.url = Some(String::from(url.trim()));
+            } else if let Some(update) = RefUpdate::parse(line) {
+                // Branches fetched by name for a pull, not an actual update
+                if update.to != "FETCH_HEAD" {
+                    result.updates.push(update);
+                }
+            }
+        }
+        result
+    }
+
+    fn count(&self, kinds: &[RefUpdateKind]) -> usize {
+        self.updates
+            .iter()
+            .filter(|u| kinds.contains(&u.kind))
+            .count()
+    }
+}
+
+impl PullResult {
+    /// Parses what git pull writes to stdout and stderr.
+    pub fn parse(stdout: &str, stderr: &str) -> PullResult {
+        let mut result = PullResult {
+            fetch: FetchResult::parse(stderr),
+            outcome: PullOutcome::Unknown,
+            range: None,
+            stat: None,
+        };
+
+        for line in stdout.lines().chain(stderr.lines()) {
+            let line = line.trim();
+            if line.starts_with("Already up to date") || line.starts_with("Current branch") {
+                result.outcome = PullOutcome::UpToDate;
+            } else if let Some(range) = line.strip_prefix("Updating ") {
+                result.range = Some(String::from(range));
+            } else if line == "Fast-forward" {
+                result.outcome = PullOutcome::FastForward;
+            } else if line.starts_with("Merge made by") {
+                result.outcome = PullOutcome::Merged;
+            } else if line.starts_with("Successfully rebased") {
+                result.outcome = PullOutcome::Rebased;
+            } else if line.contains(" changed, ") || line.ends_with(" changed") {
+                result.stat = Some(String::from(line));
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for FetchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let new = self.count(&[RefUpdateKind::New]);
+        let updated = self.count(&[
+            RefUpdateKind::FastForward,
+            RefUpdateKind::Forced,
+            RefUpdateKind::TagUpdate,
+        ]);
+        let pruned = self.count(&[RefUpdateKind::Pruned]);
+        let rejected = self.count(&[RefUpdateKind::Rejected]);
+
+        if new + updated + pruned + rejected == 0 {
+            return write!(f, "Nothing new to fetch");
+        }
+
+        let counts: Vec<String> = [
+            (new, "new"),
+            (updated, "updated"),
+            (pruned, "pruned"),
+            (rejected, "rejected"),
+        ]
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, what)| format!("{} {}", n, what))
+        .collect();
+
+        match &self.url {
+            Some(url) => write!(f, "Fetched {}: {}", url, counts.join(", "))?,
+            None => write!(f, "Fetched {}", counts.join(", "))?,
+        }
+
+        let changed: Vec<&RefUpdate> = self
+            .updates
+            .iter()
+            .filter(|u| u.kind != RefUpdateKind::UpToDate)
+            .collect();
+        for update in changed.iter().take(MAX_LISTED_UPDATES) {
+            write!(f, "\n  {} {} -> {}", update.summary, update.from, update.to)?;
+        }
+        if changed.len() > MAX_LISTED_UPDATES {
+            write!(f, "\n  and {} more", changed.len() - MAX_LISTED_UPDATES)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PullResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.outcome {
+            PullOutcome::UpToDate => return write!(f, "Already up to date"),
+            PullOutcome::FastForward => write!(f, "Fast-forwarded")?,
+            PullOutcome::Merged => write!(f, "Merged")?,
+            PullOutcome::Rebased => write!(f, "Rebased")?,
+            PullOutcome::Unknown => write!(f, "Pulled")?,
+        }
+        if let Some(range) = &self.range {
+            write!(f, " {}", range)?;
+        }
+        if let Some(stat) = &self.stat {
+            write!(f, ": {}", stat)?;
+        }
+        if !self.fetch.updates.is_empty() {
+            write!(f, "\n{}", self.fetch)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use super::Operation;
+
+/// A commit a cherry-pick or revert of several commits still has to
+/// apply, as listed in `.git/sequencer/todo`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequencerLine {
+    /// `Operation::CherryPick` or `Operation::Revert`
+    pub op: Operation,
+    /// Abbreviated hash
+    pub oid: String,
+    pub subject: String,
+}
+
+/// How a cherry-pick or revert went
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sequenced {
+    Done,
+    /// It stopped on a commit that conflicted or came out empty, the rest
+    /// are left for `--continue`
+    Stopped,
+}
+
+impl SequencerLine {
+    /// Parses the todo list of the sequencer. The commit it stopped on, if
+    /// any, is the first.
+    pub fn parse_list(todo: &str) -> Vec<SequencerLine> {
+        todo.lines()
+            .filter_map(|line| {
+                let mut words = line.splitn(3, ' ');
+                let op = match words.next()? {
+                    "pick" | "p" => Operation::CherryPick,
+                    "revert" => Operation::Revert,
+                    _ => return None,
+                };
+                Some(SequencerLine {
+                    op,
+                    oid: String::from(words.next()?),
+                    subject: String::from(words.next().unwrap_or_default()),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,36 @@
+/// Format passed to `git stash list`, the fields are separated by a NUL byte
+pub const STASH_FORMAT: &str = "%gd%x00%s";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stash {
+    /// `stash@{n}`, which changes as stashes are added and dropped
+    pub name: String,
+    /// `WIP on main: a1b2c3d Subject` or the message given when stashing
+    pub message: String,
+}
+
+/// How applying or popping a stash went
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StashApplied {
+    Clean,
+    /// The changes were applied but some conflicted. A popped stash is
+    /// kept in that case.
+    Conflicts,
+}
+
+impl Stash {
+    /// Parses the output of `git stash list` run with `STASH_FORMAT`, newest
+    /// stash first.
+    pub fn parse_list(output: &str) -> Vec<Stash> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (name, message) = line.split_once('\0')?;
+                Some(Stash {
+                    name: String::from(name),
+                    message: String::from(message),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,384 @@
+use super::SequencerLine;
+use crate::util::path_from_bytes;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State of a path on one side of the `XY` field reported by
+/// `git status --porcelain=v2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileState {
+    Unmodified,
+    Modified,
+    TypeChanged,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+    Unmerged,
+    Untracked,
+    Ignored,
+}
+
+/// Which sides of a merge touched an unmerged path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conflict {
+    BothDeleted,
+    AddedByUs,
+    DeletedByThem,
+    AddedByThem,
+    DeletedByUs,
+    BothAdded,
+    BothModified,
+}
+
+/// One side of a merge, `HEAD` being ours and the commit merged in theirs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+/// An operation that stopped for the user to resolve conflicts, or to do
+/// something else before it is continued
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Merge,
+    Rebase,
+    /// `git am`
+    ApplyMailbox,
+    CherryPick,
+    Revert,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubmoduleState {
+    pub commit_changed: bool,
+    pub modified: bool,
+    pub untracked: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusEntry {
+    pub path: PathBuf,
+    /// Source path of a rename or copy
+    pub orig_path: Option<PathBuf>,
+    pub index: FileState,
+    pub worktree: FileState,
+    pub submodule: Option<SubmoduleState>,
+    pub conflict: Option<Conflict>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BranchInfo {
+    /// `None` before the first commit
+    pub oid: Option<String>,
+    /// `None` when HEAD is detached
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RepoStatus {
+    pub branch: BranchInfo,
+    pub entries: Vec<StatusEntry>,
+}
+
+impl FileState {
+    fn from_char(c: char) -> FileState {
+        match c {
+            'M' => FileState::Modified,
+            'T' => FileState::TypeChanged,
+            'A' => FileState::Added,
+            'D' => FileState::Deleted,
+            'R' => FileState::Renamed,
+            'C' => FileState::Copied,
+            'U' => FileState::Unmerged,
+            '?' => FileState::Untracked,
+            '!' => FileState::Ignored,
+            _ => FileState::Unmodified,
+        }
+    }
+}
+
+impl Conflict {
+    fn from_xy(xy: &str) -> Option<Conflict> {
+        match xy {
+            "DD" => Some(Conflict::BothDeleted),
+            "AU" => Some(Conflict::AddedByUs),
+            "UD" => Some(Conflict::DeletedByThem),
+            "UA" => Some(Conflict::AddedByThem),
+            "DU" => Some(Conflict::DeletedByUs),
+            "AA" => Some(Conflict::BothAdded),
+            "UU" => Some(Conflict::BothModified),
+            _ => None,
+        }
+    }
+
+    /// How `git status` describes it
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Conflict::BothDeleted => "both deleted",
+            Conflict::AddedByUs => "added by us",
+            Conflict::DeletedByThem => "deleted by them",
+            Conflict::AddedByThem => "added by them",
+            Conflict::DeletedByUs => "deleted by us",
+            Conflict::BothAdded => "both added",
+            Conflict::BothModified => "both modified",
+        }
+    }
+
+    /// Whether `side` has a version of the file to take
+    pub fn has(&self, side: Side) -> bool {
+        match self {
+            Conflict::BothDeleted => false,
+            Conflict::AddedByUs | Conflict::DeletedByThem => side == Side::Ours,
+            Conflict::AddedByThem | Conflict::DeletedByUs => side == Side::Theirs,
+            Conflict::BothAdded | Conflict::BothModified => true,
+        }
+    }
+}
+
+impl Side {
+    /// The flag `git checkout` takes for it
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Side::Ours => "--ours",
+            Side::Theirs => "--theirs",
+        }
+    }
+}
+
+impl Operation {
+    /// Tells what is in progress from the state files in `git_dir`
+    pub fn detect(git_dir: &Path) -> Option<Operation> {
+        if git_dir.join("rebase-merge").is_dir() {
+            Some(Operation::Rebase)
+        } else if git_dir.join("rebase-apply").join("applying").is_file() {
+            Some(Operation::ApplyMailbox)
+        } else if git_dir.join("rebase-apply").is_dir() {
+            Some(Operation::Rebase)
+        } else if git_dir.join("MERGE_HEAD").is_file() {
+            Some(Operation::Merge)
+        } else if git_dir.join("CHERRY_PICK_HEAD").is_file() {
+            Some(Operation::CherryPick)
+        } else if git_dir.join("REVERT_HEAD").is_file() {
+            Some(Operation::Revert)
+        } else {
+            // Committing the resolution of a conflict by hand removes the
+            // head files, but the rest of the commits are still to apply
+            let todo = fs::read_to_string(git_dir.join("sequencer").join("todo")).ok()?;
+            SequencerLine::parse_list(&todo).first().map(|l| l.op)
+        }
+    }
+
+    /// Whether the commit it stopped on can be skipped, merges can only
+    /// be continued or aborted
+    pub fn can_skip(&self) -> bool {
+        *self != Operation::Merge
+    }
+
+    /// The git command continuing or aborting it
+    pub fn command(&self) -> &'static str {
+        match self {
+            Operation::Merge => "merge",
+            Operation::Rebase => "rebase",
+            Operation::ApplyMailbox => "am",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+        }
+    }
+}
+
+impl SubmoduleState {
+    /// Parses the `<sub>` field, `N...` for regular files and `S<c><m><u>`
+    /// for submodules.
+    fn parse(field: &str) -> Option<SubmoduleState> {
+        let flags: Vec<char> = field.chars().collect();
+        if flags.len() != 4 || flags[0] != 'S' {
+            return None;
+        }
+        Some(SubmoduleState {
+            commit_changed: flags[1] == 'C',
+            modified: flags[2] == 'M',
+            untracked: flags[3] == 'U',
+        })
+    }
+
+    /// What changed in the submodule, worded like `git status` does
+    pub fn describe(&self) -> String {
+        [
+            (self.commit_changed, "new commits"),
+            (self.modified, "modified content"),
+            (self.untracked, "untracked content"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, what)| *what)
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl StatusEntry {
+    fn new(xy: &[u8], sub: &[u8], path: &[u8]) -> Option<StatusEntry> {
+        let mut states = xy.iter().map(|c| FileState::from_char(*c as char));
+        Some(StatusEntry {
+            path: path_from_bytes(path),
+            orig_path: None,
+            index: states.next()?,
+            worktree: states.next()?,
+            submodule: SubmoduleState::parse(&String::from_utf8_lossy(sub)),
+            conflict: None,
+        })
+    }
+
+    fn untracked(path: &[u8], state: FileState) -> StatusEntry {
+        StatusEntry {
+            path: path_from_bytes(path),
+            orig_path: None,
+            index: state,
+            worktree: state,
+            submodule: None,
+            conflict: None,
+        }
+    }
+
+    /// The paths to restore to discard its changes, from the index as well
+    /// as the work tree if `staged`. Undoing a staged rename or copy needs
+    /// the path it came from too, otherwise the old file stays deleted. The
+    /// work tree alone only knows the new path.
+    pub fn restore_paths(&self, staged: bool) -> Vec<&Path> {
+        let orig_path = self.orig_path.as_deref().filter(|_| staged);
+        std::iter::once(self.path.as_path())
+            .chain(orig_path)
+            .collect()
+    }
+
+    pub fn is_untracked(&self) -> bool {
+        self.index == FileState::Untracked
+    }
+
+    pub fn is_unmerged(&self) -> bool {
+        self.conflict.is_some()
+    }
+
+    pub fn is_staged(&self) -> bool {
+        !self.is_unmerged()
+            && !matches!(
+                self.index,
+                FileState::Unmodified | FileState::Untracked | FileState::Ignored
+            )
+    }
+
+    pub fn is_unstaged(&self) -> bool {
+        !self.is_unmerged()
+            && !matches!(
+                self.worktree,
+                FileState::Unmodified | FileState::Untracked | FileState::Ignored
+            )
+    }
+}
+
+impl RepoStatus {
+    /// Parses the output of `git status --porcelain=v2 -z --branch`.
+    /// Records that can't be understood are skipped rather than treated as
+    /// fatal, the rest of the status is still useful. Paths are kept as the
+    /// bytes git printed, they don't have to be UTF-8.
+    pub fn parse(output: &[u8]) -> RepoStatus {
+        let mut status = RepoStatus::default();
+        let mut records = output.split(|b| *b == b'\0');
+
+        while let Some(record) = records.next() {
+            let fields = |n| record.splitn(n, |b| *b == b' ').collect::<Vec<_>>();
+            let (kind, rest) = match fields(2)[..] {
+                [k, r] => (k, r),
+                _ => continue,
+            };
+
+            match kind {
+                b"#" => status.branch.parse_header(&String::from_utf8_lossy(rest)),
+                b"1" => {
+                    let f = fields(9);
+                    if f.len() == 9 {
+                        status.entries.extend(StatusEntry::new(f[1], f[2], f[8]));
+                    }
+                }
+                b"2" => {
+                    let f = fields(10);
+                    // The original path is always sent as the next record,
+                    // consume it even if this one turns out to be malformed
+                    let orig_path = records.next();
+                    if f.len() == 10 {
+                        if let Some(mut entry) = StatusEntry::new(f[1], f[2], f[9]) {
+                            entry.orig_path = orig_path.map(path_from_bytes);
+                            status.entries.push(entry);
+                        }
+                    }
+                }
+                b"u" => {
+                    let f = fields(11);
+                    if f.len() == 11 {
+                        if let Some(mut entry) = StatusEntry::new(f[1], f[2], f[10]) {
+                            entry.conflict = Conflict::from_xy(&String::from_utf8_lossy(f[1]));
+                            status.entries.push(entry);
+                        }
+                    }
+                }
+                b"?" => status
+                    .entries
+                    .push(StatusEntry::untracked(rest, FileState::Untracked)),
+                b"!" => status
+                    .entries
+                    .push(StatusEntry::untracked(rest, FileState::Ignored)),
+                _ => {}
+            }
+        }
+
+        status
+    }
+
+    pub fn untracked(&self) -> impl Iterator<Item = &StatusEntry> {
+        self.entries.iter().filter(|e| e.is_untracked())
+    }
+
+    pub fn staged(&self) -> impl Iterator<Item = &StatusEntry> {
+        self.entries.iter().filter(|e| e.is_staged())
+    }
+
+    pub fn unstaged(&self) -> impl Iterator<Item = &StatusEntry> {
+        self.entries.iter().filter(|e| e.is_unstaged())
+    }
+
+    pub fn unmerged(&self) -> impl Iterator<Item = &StatusEntry> {
+        self.entries.iter().filter(|e| e.is_unmerged())
+    }
+}
+
+impl BranchInfo {
+    fn parse_header(&mut self, header: &str) {
+        let mut fields = header.splitn(2, ' ');
+        let (key, value) = match (fields.next(), fields.next()) {
+            (Some(k), Some(v)) => (k, v),
+            _ => return,
+        };
+
+        match key {
+            "branch.oid" if value != "(initial)" => self.oid = Some(String::from(value)),
+            "branch.head" if value != "(detached)" => self.head = Some(String::from(value)),
+            "branch.upstream" => self.upstream = Some(String::from(value)),
+            "branch.ab" => {
+                for count in value.split(' ') {
+                    if let Some(n) = count.strip_prefix('+') {
+                        self.ahead = n.parse().unwrap_or(0);
+                    } else if let Some(n) = count.strip_prefix('-') {
+                        self.behind = n.parse().unwrap_or(0);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
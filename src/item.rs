@@ -0,0 +1,90 @@
+use crate::win::ListRow;
+
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Section {
+    Unmerged,
+    Untracked,
+    Staged,
+    Unstaged,
+    Stashes,
+}
+
+/// A row of the status view the cursor can be on
+#[derive(Clone, PartialEq, Debug)]
+pub struct Item {
+    pub section: Section,
+    /// The file it belongs to, or the name of the stash
+    pub path: PathBuf,
+    pub row: ListRow,
+    /// The row of the status layer it is drawn on
+    pub y: i32,
+}
+
+impl Item {
+    /// Whether `other` is the same file, hunk or hunk line, wherever the
+    /// file ended up in its list
+    pub fn same_place(&self, other: &Item) -> bool {
+        let place = |row| match row {
+            ListRow::File(_) => (None, None),
+            ListRow::Hunk(_, h) => (Some(h), None),
+            ListRow::Line(_, h, l) => (Some(h), Some(l)),
+        };
+        self.section == other.section
+            && self.path == other.path
+            && place(self.row) == place(other.row)
+    }
+}
+
+/// Item `i`, or the last one if there aren't as many
+pub fn item_at(items: &[Item], i: usize) -> Option<&Item> {
+    items.get(i).or_else(|| items.last())
+}
+
+/// The first item of the section after, or before, the one of item `i`.
+/// `None` if there is no such section.
+pub fn section_jump(items: &[Item], i: usize, forward: bool) -> Option<usize> {
+    let section = items.get(i)?.section;
+    if forward {
+        items[i..]
+            .iter()
+            .position(|item| item.section != section)
+            .map(|n| i + n)
+    } else {
+        match items.iter().position(|item| item.section == section) {
+            Some(start) if start > 0 => {
+                let previous = items[start - 1].section;
+                items.iter().position(|item| item.section == previous)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where `cursor` goes after `items` were rebuilt from `old`: back on the
+/// same row if it still exists, otherwise on its file. A file that left
+/// its section, by being staged for instance, leaves the cursor on the
+/// closest of the items around it that are still there.
+pub fn find_cursor<'a>(items: &'a [Item], old: &[Item], cursor: &Item) -> Option<&'a Item> {
+    let find = |wanted: &Item| items.iter().find(|item| item.same_place(wanted));
+
+    let found = find(cursor).or_else(|| {
+        items.iter().find(|item| {
+            item.section == cursor.section
+                && item.path == cursor.path
+                && matches!(item.row, ListRow::File(_))
+        })
+    });
+    if found.is_some() {
+        return found;
+    }
+
+    let i = old.iter().position(|item| item == cursor).unwrap_or(0);
+    let others = |item: &&Item| item.path != cursor.path;
+    old[i..]
+        .iter()
+        .filter(others)
+        .find_map(find)
+        .or_else(|| old[..i].iter().rev().filter(others).find_map(find))
+}
@@ -0,0 +1,195 @@
+use pancurses::Input;
+
+use std::fmt;
+
+/// A single key press. Chords in the config file and the keys read from
+/// the terminal both end up as these, so the two can be compared directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    /// Control held down together with a lower case letter, `<C-x>`. Keys
+    /// the terminal sends the same bytes for, like `<C-i>` and `<Tab>`,
+    /// are the named key instead, see `Key::ctrl`
+    Ctrl(char),
+    /// Alt or Meta held down together with a character, `<M-x>`
+    Alt(char),
+    Tab,
+    Enter,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Delete,
+    /// `<F1>` to `<F12>`
+    F(u8),
+}
+
+impl Key {
+    /// Control together with `c`, as the terminal reports it.
+    pub fn ctrl(c: char) -> Key {
+        match c.to_ascii_lowercase() {
+            'i' => Key::Tab,
+            'j' | 'm' => Key::Enter,
+            'h' => Key::Backspace,
+            '[' => Key::Esc,
+            c => Key::Ctrl(c),
+        }
+    }
+
+    /// The key a raw character from the terminal stands for. Control keys
+    /// arrive as the ASCII control codes.
+    pub fn from_char(c: char) -> Key {
+        match c as u32 {
+            0x09 => Key::Tab,
+            0x0a | 0x0d => Key::Enter,
+            0x08 | 0x7f => Key::Backspace,
+            0x1b => Key::Esc,
+            code @ 0x01..=0x1a => Key::Ctrl((b'a' + code as u8 - 1) as char),
+            _ => Key::Char(c),
+        }
+    }
+
+    /// The key for a pancurses input, `None` for input that isn't one
+    /// this program knows about, like mouse events.
+    pub fn from_input(input: Input) -> Option<Key> {
+        let key = match input {
+            Input::Character(c) => Key::from_char(c),
+            Input::KeyBackspace => Key::Backspace,
+            Input::KeyEnter => Key::Enter,
+            Input::KeyUp => Key::Up,
+            Input::KeyDown => Key::Down,
+            Input::KeyLeft => Key::Left,
+            Input::KeyRight => Key::Right,
+            Input::KeyPPage => Key::PageUp,
+            Input::KeyNPage => Key::PageDown,
+            Input::KeyHome => Key::Home,
+            Input::KeyEnd => Key::End,
+            Input::KeyDC => Key::Delete,
+            Input::KeyF1 => Key::F(1),
+            Input::KeyF2 => Key::F(2),
+            Input::KeyF3 => Key::F(3),
+            Input::KeyF4 => Key::F(4),
+            Input::KeyF5 => Key::F(5),
+            Input::KeyF6 => Key::F(6),
+            Input::KeyF7 => Key::F(7),
+            Input::KeyF8 => Key::F(8),
+            Input::KeyF9 => Key::F(9),
+            Input::KeyF10 => Key::F(10),
+            Input::KeyF11 => Key::F(11),
+            Input::KeyF12 => Key::F(12),
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    /// Parses the inside of a `<...>` key name, case doesn't matter.
+    fn from_name(name: &str) -> Option<Key> {
+        let lower = name.to_ascii_lowercase();
+        let key = match lower.as_str() {
+            "lt" => Key::Char('<'),
+            "space" => Key::Char(' '),
+            "tab" => Key::Tab,
+            "cr" | "enter" | "return" => Key::Enter,
+            "bs" => Key::Backspace,
+            "esc" => Key::Esc,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "del" => Key::Delete,
+            _ => {
+                if let Some(n) = lower.strip_prefix('f') {
+                    return match n.parse() {
+                        Ok(n) if (1..=12).contains(&n) => Some(Key::F(n)),
+                        _ => None,
+                    };
+                }
+
+                // `<C-x>` and `<M-x>` keep the case of the character
+                let (modifier, c) = name.split_once('-')?;
+                let mut chars = c.chars();
+                let c = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return None,
+                };
+                match modifier {
+                    "C" | "c" if c.is_ascii_alphabetic() || c == '[' => Key::ctrl(c),
+                    "M" | "m" | "A" | "a" => Key::Alt(c),
+                    _ => return None,
+                }
+            }
+        };
+        Some(key)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Char(' ') => write!(f, "<Space>"),
+            Key::Char('<') => write!(f, "<lt>"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Ctrl(c) => write!(f, "<C-{}>", c),
+            Key::Alt(c) => write!(f, "<M-{}>", c),
+            Key::Tab => write!(f, "<Tab>"),
+            Key::Enter => write!(f, "<CR>"),
+            Key::Backspace => write!(f, "<BS>"),
+            Key::Esc => write!(f, "<Esc>"),
+            Key::Up => write!(f, "<Up>"),
+            Key::Down => write!(f, "<Down>"),
+            Key::Left => write!(f, "<Left>"),
+            Key::Right => write!(f, "<Right>"),
+            Key::PageUp => write!(f, "<PageUp>"),
+            Key::PageDown => write!(f, "<PageDown>"),
+            Key::Home => write!(f, "<Home>"),
+            Key::End => write!(f, "<End>"),
+            Key::Delete => write!(f, "<Del>"),
+            Key::F(n) => write!(f, "<F{}>", n),
+        }
+    }
+}
+
+/// Parses a chord as written in the config, like `gg`, `<C-n>` or
+/// `<Space>f`. Characters outside `<...>` are keys of their own.
+pub fn parse_chord(chord: &str) -> Result<Vec<Key>, String> {
+    if chord.is_empty() {
+        return Err(String::from("empty key chord"));
+    }
+
+    let mut keys = vec![];
+    let mut rest = chord;
+    while let Some(c) = rest.chars().next() {
+        if c != '<' {
+            keys.push(Key::Char(c));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => return Err(format!("unclosed `<` in key chord \"{}\"", chord)),
+        };
+        let name = &rest[1..end];
+        match Key::from_name(name) {
+            Some(key) => keys.push(key),
+            None => return Err(format!("unknown key <{}> in key chord \"{}\"", name, chord)),
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(keys)
+}
+
+/// Writes `keys` back the way `parse_chord` reads them.
+pub fn chord_to_string(keys: &[Key]) -> String {
+    keys.iter().map(|k| k.to_string()).collect()
+}
@@ -1,8 +1,13 @@
+mod args;
 mod config;
 mod controller;
+mod editor;
 mod git;
+mod item;
+mod key;
 mod mode;
 mod tests;
+mod theme;
 mod util;
 mod win;
 
@@ -17,8 +22,17 @@ use controller::set_escdelay;
 use controller::Controller;
 
 use std::env::{self, consts};
+use std::panic;
 
 fn main() {
+    // Leave raw mode before the panic message is printed, otherwise the
+    // terminal is unusable afterwards
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        endwin();
+        default_hook(info);
+    }));
+
     let args: Vec<String> = env::args().collect();
     let mut controller = Controller::new(env::current_dir().unwrap());
 
@@ -34,11 +48,13 @@ fn main() {
 
     controller.render();
     while controller.running() {
-        controller.handle_key(match controller.win.win.getch() {
-            Some(Input::Character(c)) => c as i32,
-            Some(Input::KeyBackspace) => '\u{107}' as i32,
-            _ => 0,
-        });
+        // getch gives up after a while when a job needs checking on
+        if let Some(key) = controller.win.read_key() {
+            controller.handle_key(key);
+        }
+        controller.poll_job();
+        // ncurses has already resized its windows when the terminal did
+        controller.fit_to_screen();
         controller.render();
     }
 
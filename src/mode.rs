@@ -1,37 +1,153 @@
-use std::char;
+use crate::editor::TextBuffer;
+use crate::key::{chord_to_string, parse_chord, Key};
+
 use std::iter::zip;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum Action {
-    ConfirmCommitMsg,
-    CursorBufferEnd,
-    CursorBufferStart,
-    CursorDown,
-    CursorUp,
-    Error,
-    Exit,
-    Matching,
-    NoMatch,
-    OpenCommitMode,
-    OpenCommitMsgMode,
-    OpenHelpMode,
-    Push,
-    StageAllFiles,
-    StageFile,
-    ToggleCommitAllowEmpty,
-    ToggleCommitDisableHooks,
-    ToggleCommitResetAuthor,
-    ToggleCommitStageAll,
-    ToggleCommitVerbose,
-    UnstageFile,
-    WriteChar,
+/// Declares `Action` and `BINDABLE_ACTIONS` from a single list, so every
+/// action that isn't internal can be bound in the config file
+macro_rules! actions {
+    (internal: [$($internal:ident),* $(,)?], bindable: [$($action:ident),* $(,)?] $(,)?) => {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum Action {
+            $($action,)*
+            $($internal,)*
+        }
+
+        /// Actions that can be bound to keys in the config file, by the name
+        /// the file calls them
+        const BINDABLE_ACTIONS: &[(Action, &str)] =
+            &[$((Action::$action, stringify!($action)),)*];
+    };
+}
+
+actions! {
+    // Returned by modes and the editor, not bound to keys
+    internal: [Matching, NoMatch, WriteChar],
+    bindable: [
+        AbortOperation,
+        CancelJob,
+        CheckoutBranch,
+        CherryPick,
+        CommitAmend,
+        CommitExtend,
+        CommitFixup,
+        CommitInEditor,
+        CommitReword,
+        CommitSquash,
+        Confirm,
+        ConfirmCommitMsg,
+        ContinueOperation,
+        CursorBufferEnd,
+        CursorBufferStart,
+        CursorDown,
+        CursorLeft,
+        CursorLineEnd,
+        CursorLineStart,
+        CursorRight,
+        CursorUp,
+        CursorWordBackward,
+        CursorWordForward,
+        CreateBranch,
+        DeleteBranch,
+        Discard,
+        Exit,
+        FetchElsewhere,
+        FetchUpstream,
+        Mergetool,
+        NextSection,
+        OpenBranchMode,
+        OpenCherryPickMode,
+        OpenCommitMode,
+        OpenCommitMsgMode,
+        OpenFetchMode,
+        OpenHelpMode,
+        OpenLogMode,
+        OpenPullMode,
+        OpenPushMode,
+        OpenResetMode,
+        OpenRevertMode,
+        OpenStashMode,
+        PrevSection,
+        PullElsewhere,
+        PullUpstream,
+        Push,
+        PushElsewhere,
+        RebaseDrop,
+        RebaseEdit,
+        RebaseFixup,
+        RebaseInteractive,
+        RebaseMoveDown,
+        RebaseMoveUp,
+        RebasePick,
+        RebaseReword,
+        RebaseSquash,
+        RenameBranch,
+        Reset,
+        ResetElsewhere,
+        Revert,
+        ShowCommit,
+        SkipOperation,
+        StageAllFiles,
+        StageFile,
+        Stash,
+        StashApply,
+        StashDrop,
+        StashPop,
+        StashShow,
+        TakeOurs,
+        TakeTheirs,
+        ToggleCherryPickEdit,
+        ToggleCherryPickMainline,
+        ToggleCherryPickNoCommit,
+        ToggleCherryPickReference,
+        ToggleCommitAllowEmpty,
+        ToggleCommitDisableHooks,
+        ToggleCommitResetAuthor,
+        ToggleCommitStageAll,
+        ToggleCommitVerbose,
+        ToggleExpand,
+        ToggleFetchAll,
+        ToggleFetchPrune,
+        ToggleFetchTags,
+        ToggleLogGraph,
+        TogglePullAutostash,
+        TogglePullFfOnly,
+        TogglePullRebase,
+        TogglePushDisableHooks,
+        TogglePushForceWithLease,
+        TogglePushSetUpstream,
+        TogglePushTags,
+        ToggleResetHard,
+        ToggleResetKeep,
+        ToggleResetMixed,
+        ToggleResetSoft,
+        ToggleRevertEdit,
+        ToggleRevertMainline,
+        ToggleRevertNoCommit,
+        ToggleStashAll,
+        ToggleStashIncludeUntracked,
+        ToggleStashKeepIndex,
+        ToggleVisualSelect,
+        UnstageFile,
+    ],
+}
+
+impl Action {
+    /// The action called `name` in the config file, which is the name of
+    /// the variant
+    pub fn from_name(name: &str) -> Option<Action> {
+        BINDABLE_ACTIONS
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(action, _)| *action)
+    }
 }
 
 pub trait Mode {
     fn new() -> Self
     where
         Self: Sized;
-    fn handle_key(&mut self, key: i32) -> Action;
+    fn handle_key(&mut self, key: Key) -> Action;
 
     fn get_bound_chords(&self) -> Vec<String>;
     fn get_bound_actions(&self) -> Vec<Action>;
@@ -40,19 +156,10 @@ pub trait Mode {
     fn set_key_map(&mut self, bindings: Vec<(String, Action)>);
 }
 
-pub fn config_str_to_term_str(ch: &str) -> String {
-    // TODO: Complete with conversion for Ctrl
-    let mut chord = String::from(ch);
-    chord = chord.replace("<Esc>", &format!("{}", 27 as char));
-    chord = chord.replace("<Space>", " ");
-
-    return chord;
-}
-
 pub struct StageMode {
-    keys: Vec<String>,
+    keys: Vec<Vec<Key>>,
     bound_fns: Vec<Action>,
-    chord: String,
+    chord: Vec<Key>,
     longest_chord: usize,
 }
 
@@ -64,28 +171,15 @@ impl Mode for StageMode {
         StageMode {
             keys: Vec::new(),
             bound_fns: Vec::new(),
-            chord: String::new(),
+            chord: Vec::new(),
             longest_chord: 0,
         }
     }
 
-    fn handle_key(&mut self, key: i32) -> Action {
-        // Not a key press
-        if key < 0 {
-            self.chord.clear();
-            return Action::Error;
-        }
-
-        let pressed = char::from_u32(key as u32);
-        // Not all u32s are valid keys
-        if pressed.is_none() {
-            self.chord.clear();
-            return Action::Error;
-        }
-
-        self.chord.push(pressed.unwrap());
+    fn handle_key(&mut self, key: Key) -> Action {
+        self.chord.push(key);
         // No matching key binding
-        if self.chord.chars().count() > self.longest_chord {
+        if self.chord.len() > self.longest_chord {
             self.chord.clear();
             return Action::NoMatch;
         }
@@ -104,38 +198,43 @@ impl Mode for StageMode {
         // Return self.error_func means that there is no point in trying to
         // investigate the current chord any further
         if potential_match {
-            return Action::Matching;
+            Action::Matching
         } else {
             self.chord.clear();
-            return Action::NoMatch;
+            Action::NoMatch
         }
     }
 
     fn get_bound_chords(&self) -> Vec<String> {
-        return self.keys.clone();
+        self.keys.iter().map(|ch| chord_to_string(ch)).collect()
     }
 
     fn get_bound_actions(&self) -> Vec<Action> {
-        return self.bound_fns.clone();
+        self.bound_fns.clone()
     }
 
     fn set_key_map(&mut self, bindings: Vec<(String, Action)>) {
         self.longest_chord = 0;
         for (ch, fun) in bindings {
-            if ch.chars().count() > self.longest_chord {
-                self.longest_chord = ch.chars().count();
+            // The config loader has already reported chords that don't parse
+            let ch = match parse_chord(&ch) {
+                Ok(ch) => ch,
+                Err(_) => continue,
+            };
+            if ch.len() > self.longest_chord {
+                self.longest_chord = ch.len();
             }
-            self.keys.push(config_str_to_term_str(&ch));
+            self.keys.push(ch);
             self.bound_fns.push(fun);
         }
     }
 }
 
+/// Multi-line editor for the commit message. Bound chords move the cursor
+/// around or leave the editor, everything else is typed into the message.
 pub struct CommitMsgMode {
-    exit_key: char,
-    confirm_key: char,
-    backspace_keys: Vec<char>,
-    pub commit_msg: String,
+    bindings: StageMode,
+    pub buffer: TextBuffer,
 }
 
 impl Mode for CommitMsgMode {
@@ -144,48 +243,93 @@ impl Mode for CommitMsgMode {
         Self: Sized,
     {
         CommitMsgMode {
-            exit_key: 27 as char,
-            confirm_key: '\n',
-            backspace_keys: vec!['\u{107}', 8 as char],
-            commit_msg: String::new(),
+            bindings: Mode::new(),
+            buffer: TextBuffer::new(),
         }
     }
 
-    fn handle_key(&mut self, key: i32) -> Action {
-        // Not a key press
-        if key < 0 {
-            return Action::Error;
+    fn handle_key(&mut self, key: Key) -> Action {
+        let action = self.bindings.handle_key(key);
+        match action {
+            Action::NoMatch => {
+                match key {
+                    Key::Char(c) => self.buffer.insert(c),
+                    Key::Enter => self.buffer.newline(),
+                    Key::Backspace => self.buffer.backspace(),
+                    Key::Delete => self.buffer.delete(),
+                    _ => return Action::NoMatch,
+                }
+                return Action::WriteChar;
+            }
+            Action::CursorLeft => self.buffer.left(),
+            Action::CursorRight => self.buffer.right(),
+            Action::CursorUp => self.buffer.up(),
+            Action::CursorDown => self.buffer.down(),
+            Action::CursorLineStart => self.buffer.line_start(),
+            Action::CursorLineEnd => self.buffer.line_end(),
+            Action::CursorWordForward => self.buffer.word_forward(),
+            Action::CursorWordBackward => self.buffer.word_backward(),
+            _ => {}
         }
+        action
+    }
 
-        let pressed = char::from_u32(key as u32);
-        // Not all u32s are valid keys
-        if pressed.is_none() {
-            return Action::Error;
+    fn get_bound_chords(&self) -> Vec<String> {
+        self.bindings.get_bound_chords()
+    }
+
+    fn get_bound_actions(&self) -> Vec<Action> {
+        self.bindings.get_bound_actions()
+    }
+
+    fn set_key_map(&mut self, bindings: Vec<(String, Action)>) {
+        self.bindings.set_key_map(bindings);
+    }
+}
+
+/// Single line text input for prompts, confirmed with enter
+pub struct InputMode {
+    exit_key: Key,
+    confirm_key: Key,
+    pub input: String,
+}
+
+impl Mode for InputMode {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        InputMode {
+            exit_key: Key::Esc,
+            confirm_key: Key::Enter,
+            input: String::new(),
         }
+    }
 
-        let c = pressed.unwrap();
-        if c == self.exit_key {
-            return Action::Exit;
-        } else if c == self.confirm_key {
-            return Action::ConfirmCommitMsg;
-        } else if self.backspace_keys.contains(&c) {
-            self.commit_msg.pop();
-            return Action::WriteChar;
+    fn handle_key(&mut self, key: Key) -> Action {
+        if key == self.exit_key {
+            Action::Exit
+        } else if key == self.confirm_key {
+            Action::Confirm
         } else {
-            self.commit_msg.push(c);
-            return Action::WriteChar;
+            match key {
+                Key::Backspace => {
+                    self.input.pop();
+                }
+                Key::Char(c) => self.input.push(c),
+                _ => return Action::NoMatch,
+            }
+            Action::WriteChar
         }
     }
 
     fn get_bound_chords(&self) -> Vec<String> {
-        return vec![String::from(self.exit_key), String::from(self.confirm_key)];
+        vec![self.exit_key.to_string(), self.confirm_key.to_string()]
     }
 
     fn get_bound_actions(&self) -> Vec<Action> {
-        return vec![Action::Exit, Action::ConfirmCommitMsg];
+        vec![Action::Exit, Action::Confirm]
     }
 
-    fn set_key_map(&mut self, _bindings: Vec<(String, Action)>) {
-        // TODO: Consider adding behaviour here
-    }
+    fn set_key_map(&mut self, _bindings: Vec<(String, Action)>) {}
 }
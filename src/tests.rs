@@ -1,19 +1,64 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
+    use crate::args::*;
+    use crate::config::*;
+    use crate::editor::*;
+    use crate::git::*;
+    use crate::item::*;
+    use crate::key::*;
     use crate::mode::*;
+    use crate::theme::*;
+    use crate::util::*;
+    use crate::win::{ArgList, ListRow, UiElement};
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
 
     #[test]
-    fn config_str_to_term_str_converts_space_esc() {
+    fn parse_chord_converts_space_esc() {
         let cfg1 = "<Esc>cc";
         let cfg2 = "<Space>Eg";
 
-        assert_eq!(config_str_to_term_str(cfg1), format!("{}cc", 27 as char));
-        assert_eq!(config_str_to_term_str(cfg2), String::from(" Eg"));
+        assert_eq!(
+            parse_chord(cfg1),
+            Ok(vec![Key::Esc, Key::Char('c'), Key::Char('c')])
+        );
+        assert_eq!(
+            parse_chord(cfg2),
+            Ok(vec![Key::Char(' '), Key::Char('E'), Key::Char('g')])
+        );
+    }
+
+    #[test]
+    fn parse_chord_reads_named_keys() {
+        assert_eq!(
+            parse_chord("<C-n><M-x><Tab><CR><BS><PageUp><F12><lt>"),
+            Ok(vec![
+                Key::Ctrl('n'),
+                Key::Alt('x'),
+                Key::Tab,
+                Key::Enter,
+                Key::Backspace,
+                Key::PageUp,
+                Key::F(12),
+                Key::Char('<'),
+            ])
+        );
+        // The terminal can't tell these apart from the named keys
+        assert_eq!(parse_chord("<C-i><C-m>"), Ok(vec![Key::Tab, Key::Enter]));
+        assert_eq!(Key::from_char('\x0e'), Key::Ctrl('n'));
+
+        assert!(parse_chord("<F13>").is_err());
+        assert!(parse_chord("<C-n").is_err());
+        assert_eq!(
+            chord_to_string(&parse_chord("<c-N><space>").unwrap()),
+            "<C-n><Space>"
+        );
     }
 
     #[test]
     fn commit_mode_set_key_map() {
-        let bindings = vec![
+        let bindings = [
             ("k", Action::CursorUp),
             ("j", Action::CursorDown),
             ("gg", Action::CursorBufferStart),
@@ -28,18 +73,1095 @@ mod tests {
 
         mode.set_key_map(bindings);
 
-        assert_eq!(mode.handle_key('k' as i32), Action::CursorUp);
-        assert_eq!(mode.handle_key('j' as i32), Action::CursorDown);
-        assert_eq!(mode.handle_key('G' as i32), Action::CursorBufferEnd);
+        assert_eq!(mode.handle_key(Key::Char('k')), Action::CursorUp);
+        assert_eq!(mode.handle_key(Key::Char('j')), Action::CursorDown);
+        assert_eq!(mode.handle_key(Key::Char('G')), Action::CursorBufferEnd);
+
+        assert_eq!(mode.handle_key(Key::Char('g')), Action::Matching);
+        assert_eq!(mode.handle_key(Key::Char('g')), Action::CursorBufferStart);
+
+        assert_eq!(mode.handle_key(Key::Char('g')), Action::Matching);
+        assert_eq!(mode.handle_key(Key::Char('h')), Action::NoMatch);
+
+        assert_eq!(mode.handle_key(Key::Char('h')), Action::NoMatch);
+
+        assert_eq!(mode.handle_key(Key::Esc), Action::Exit);
+    }
+
+    #[test]
+    fn repo_status_parses_branch_and_entries() {
+        let output = [
+            "# branch.oid 1a2f4083e4b7c1b4f8e9d0c6a1b2c3d4e5f60718",
+            "# branch.head master",
+            "# branch.upstream origin/master",
+            "# branch.ab +2 -1",
+            "1 MM N... 100644 100644 100644 1111111 2222222 src/both.rs",
+            "1 .M N... 100644 100644 100644 1111111 1111111 src/unstaged.rs",
+            "2 R. N... 100644 100644 100644 3333333 3333333 R100 src/new name.rs",
+            "src/old.rs",
+            "u UU N... 100644 100644 100644 100644 4444444 5555555 6666666 conflict.rs",
+            "? untracked.txt",
+            "",
+        ]
+        .join("\0");
+
+        let status = RepoStatus::parse(output.as_bytes());
+
+        assert_eq!(status.branch.head, Some(String::from("master")));
+        assert_eq!(status.branch.upstream, Some(String::from("origin/master")));
+        assert_eq!((status.branch.ahead, status.branch.behind), (2, 1));
+        assert_eq!(status.entries.len(), 5);
+
+        let staged: Vec<&PathBuf> = status.staged().map(|e| &e.path).collect();
+        let unstaged: Vec<&PathBuf> = status.unstaged().map(|e| &e.path).collect();
+        let untracked: Vec<&PathBuf> = status.untracked().map(|e| &e.path).collect();
+        assert_eq!(
+            staged,
+            vec![
+                &PathBuf::from("src/both.rs"),
+                &PathBuf::from("src/new name.rs")
+            ]
+        );
+        assert_eq!(
+            unstaged,
+            vec![
+                &PathBuf::from("src/both.rs"),
+                &PathBuf::from("src/unstaged.rs")
+            ]
+        );
+        assert_eq!(untracked, vec![&PathBuf::from("untracked.txt")]);
+
+        let renamed = &status.entries[2];
+        assert_eq!(renamed.index, FileState::Renamed);
+        assert_eq!(renamed.orig_path, Some(PathBuf::from("src/old.rs")));
+
+        let conflicted = &status.entries[3];
+        assert_eq!(conflicted.conflict, Some(Conflict::BothModified));
+        assert!(!conflicted.is_staged() && !conflicted.is_unstaged());
+        let unmerged: Vec<&PathBuf> = status.unmerged().map(|e| &e.path).collect();
+        assert_eq!(unmerged, vec![&PathBuf::from("conflict.rs")]);
+    }
+
+    #[test]
+    fn discarding_a_rename_restores_the_old_path_from_the_index() {
+        let output = [
+            "2 RM N... 100644 100644 100644 3333333 3333333 R100 new.rs",
+            "old.rs",
+            "1 .M N... 100644 100644 100644 1111111 1111111 changed.rs",
+            "",
+        ]
+        .join("\0");
+
+        let status = RepoStatus::parse(output.as_bytes());
+
+        let renamed = &status.entries[0];
+        assert_eq!(
+            renamed.restore_paths(true),
+            [Path::new("new.rs"), Path::new("old.rs")]
+        );
+        // git doesn't know old.rs in the work tree
+        assert_eq!(renamed.restore_paths(false), [Path::new("new.rs")]);
+        assert_eq!(
+            status.entries[1].restore_paths(false),
+            [Path::new("changed.rs")]
+        );
+    }
+
+    #[test]
+    fn submodule_flags_are_described_like_git_status() {
+        let output = [
+            "1 .M SCMU 160000 160000 160000 1111111 1111111 lib/dep",
+            "1 .M S.M. 160000 160000 160000 2222222 2222222 lib/other",
+            "1 .M N... 100644 100644 100644 3333333 3333333 file.rs",
+            "",
+        ]
+        .join("\0");
+
+        let status = RepoStatus::parse(output.as_bytes());
+
+        let describe = |i: usize| status.entries[i].submodule.map(|s| s.describe());
+        assert_eq!(
+            describe(0).as_deref(),
+            Some("new commits, modified content, untracked content")
+        );
+        assert_eq!(describe(1).as_deref(), Some("modified content"));
+        assert_eq!(describe(2), None);
+    }
+
+    #[test]
+    fn conflicts_know_which_sides_have_the_file() {
+        assert!(Conflict::BothModified.has(Side::Ours));
+        assert!(Conflict::BothModified.has(Side::Theirs));
+        assert!(Conflict::DeletedByUs.has(Side::Theirs));
+        assert!(!Conflict::DeletedByUs.has(Side::Ours));
+        assert!(!Conflict::AddedByUs.has(Side::Theirs));
+        assert!(!Conflict::BothDeleted.has(Side::Ours));
+    }
+
+    #[test]
+    fn operation_is_detected_from_git_dir() {
+        let git_dir = std::env::temp_dir().join(format!("vrgit-operation-{}", std::process::id()));
+        std::fs::create_dir_all(&git_dir).unwrap();
+        assert_eq!(Operation::detect(&git_dir), None);
+
+        std::fs::write(git_dir.join("MERGE_HEAD"), "").unwrap();
+        assert_eq!(Operation::detect(&git_dir), Some(Operation::Merge));
+
+        // A rebase can stop on a conflicting pick, it's still the rebase
+        // that has to be continued
+        std::fs::create_dir(git_dir.join("rebase-merge")).unwrap();
+        assert_eq!(Operation::detect(&git_dir), Some(Operation::Rebase));
+        std::fs::remove_dir(git_dir.join("rebase-merge")).unwrap();
+        std::fs::remove_file(git_dir.join("MERGE_HEAD")).unwrap();
+
+        // Committing a resolved conflict removes REVERT_HEAD but leaves the
+        // rest of the commits to the sequencer
+        std::fs::create_dir(git_dir.join("sequencer")).unwrap();
+        std::fs::write(
+            git_dir.join("sequencer").join("todo"),
+            "revert abc1234 Subject\n",
+        )
+        .unwrap();
+        assert_eq!(Operation::detect(&git_dir), Some(Operation::Revert));
+
+        std::fs::remove_dir_all(&git_dir).unwrap();
+    }
+
+    #[test]
+    fn repo_status_handles_detached_and_garbage() {
+        let output = "# branch.oid (initial)\0# branch.head (detached)\0\0\x001 MM\0bogus\0";
+
+        let status = RepoStatus::parse(output.as_bytes());
+
+        assert_eq!(status.branch, BranchInfo::default());
+        assert!(status.entries.is_empty());
+        assert_eq!(RepoStatus::parse(b""), RepoStatus::default());
+    }
+
+    #[test]
+    fn git_error_displays_command_and_stderr() {
+        let err = GitError {
+            command: String::from("git push"),
+            status: Some(128),
+            stderr: String::from("fatal: not a git repository"),
+        };
+        let spawn_err = GitError {
+            command: String::from("git status"),
+            status: None,
+            stderr: String::new(),
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "`git push` failed with exit code 128: fatal: not a git repository"
+        );
+        assert_eq!(spawn_err.to_string(), "`git status` failed");
+    }
+
+    #[test]
+    fn git_error_tells_auth_failures() {
+        let error = |stderr: &str| GitError {
+            command: String::from("git fetch --progress"),
+            status: Some(128),
+            stderr: String::from(stderr),
+        };
 
-        assert_eq!(mode.handle_key('g' as i32), Action::Matching);
-        assert_eq!(mode.handle_key('g' as i32), Action::CursorBufferStart);
+        assert!(error(
+            "fatal: could not read Username for 'https://example.com': terminal prompts disabled"
+        )
+        .is_auth_failure());
+        assert!(error(
+            "git@example.com: Permission denied (publickey).\n\
+             fatal: Could not read from remote repository."
+        )
+        .is_auth_failure());
+        assert!(error("Host key verification failed.").is_auth_failure());
+        assert!(!error("fatal: 'origin' does not appear to be a git repository").is_auth_failure());
+    }
 
-        assert_eq!(mode.handle_key('g' as i32), Action::Matching);
-        assert_eq!(mode.handle_key('h' as i32), Action::NoMatch);
+    #[test]
+    fn git_error_tells_empty_commit_messages() {
+        let error = |stderr: &str| GitError {
+            command: String::from("git commit"),
+            status: Some(1),
+            stderr: String::from(stderr),
+        };
 
-        assert_eq!(mode.handle_key('h' as i32), Action::NoMatch);
+        // What git prints when the editor leaves the message empty
+        assert!(error("Aborting commit due to empty commit message.").is_empty_commit_message());
+        assert!(!error(
+            "Aborting commit; you did not edit the message.\n\
+             Please supply the message using either -m or -F option."
+        )
+        .is_empty_commit_message());
+        assert!(!error("nothing to commit, working tree clean").is_empty_commit_message());
+    }
+
+    #[test]
+    fn git_error_suggests_upstream_for_push() {
+        let err = GitError {
+            command: String::from("git push"),
+            status: Some(128),
+            stderr: String::from(
+                "fatal: The current branch topic has no upstream branch.\n\
+                 To push the current branch and set the remote as upstream, use\n\
+                 \n    git push --set-upstream origin topic\n",
+            ),
+        };
+        let other = GitError {
+            command: String::from("git push"),
+            status: Some(1),
+            stderr: String::from("error: failed to push some refs"),
+        };
+
+        assert_eq!(
+            err.suggested_upstream(),
+            Some((String::from("origin"), String::from("topic")))
+        );
+        assert_eq!(other.suggested_upstream(), None);
+    }
+
+    #[test]
+    fn file_diff_parses_hunks_and_lines() {
+        let text = "\
+diff --git a/src/a.rs b/src/a.rs
+index 1111111..2222222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,3 +1,3 @@ fn main() {
+ one
+-two
++TWO
+ three
+@@ -10 +10,2 @@
+ ten
++eleven
+\\ No newline at end of file
+diff --git a/bin.png b/bin.png
+Binary files a/bin.png and b/bin.png differ
+";
+
+        let files = FileDiff::parse(text.as_bytes());
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].header.len(), 4);
+        assert_eq!(files[0].hunks.len(), 2);
+
+        let first = &files[0].hunks[0];
+        assert_eq!((first.old_start, first.old_count), (1, 3));
+        assert_eq!(first.context, "fn main() {");
+        assert_eq!(first.header(), "@@ -1,3 +1,3 @@ fn main() {");
+        assert_eq!(
+            first.lines.iter().map(|l| l.kind).collect::<Vec<_>>(),
+            vec![
+                LineKind::Context,
+                LineKind::Removed,
+                LineKind::Added,
+                LineKind::Context
+            ]
+        );
+        assert_eq!(first.lines[2].content, "TWO");
+
+        let second = &files[0].hunks[1];
+        assert_eq!((second.old_start, second.old_count), (10, 1));
+        assert_eq!((second.new_start, second.new_count), (10, 2));
+        assert_eq!(second.lines[2].kind, LineKind::NoNewline);
+
+        assert!(files[1].is_binary());
+        assert!(files[1].hunks.is_empty());
+        assert!(FileDiff::parse(b"").is_empty());
+    }
+
+    fn two_line_change() -> FileDiff {
+        FileDiff::parse(
+            "\
+diff --git a/f b/f
+index 1111111..2222222 100644
+--- a/f
++++ b/f
+@@ -1,4 +1,4 @@ ctx
+ 1
+-2
+-3
++two
++three
+ 4
+"
+            .as_bytes(),
+        )
+        .remove(0)
+    }
+
+    /// `FileDiff::hunk_patch` as text
+    fn patch_text(
+        diff: &FileDiff,
+        hunk: usize,
+        selection: Option<std::ops::RangeInclusive<usize>>,
+        reverse: bool,
+    ) -> Option<String> {
+        diff.hunk_patch(hunk, selection, reverse)
+            .map(|patch| String::from_utf8(patch).unwrap())
+    }
+
+    #[test]
+    fn hunk_patch_keeps_whole_hunk_without_selection() {
+        let diff = two_line_change();
+
+        let patch = patch_text(&diff, 0, None, false).unwrap();
+
+        assert_eq!(
+            patch,
+            "diff --git a/f b/f\nindex 1111111..2222222 100644\n--- a/f\n+++ b/f\n\
+             @@ -1,4 +1,4 @@ ctx\n 1\n-2\n-3\n+two\n+three\n 4\n"
+        );
+        assert_eq!(patch_text(&diff, 1, None, false), None);
+    }
+
+    #[test]
+    fn hunk_patch_stages_selected_lines() {
+        let diff = two_line_change();
+
+        // Only "-2" and "+two": the unselected removal stays as context and
+        // the unselected addition is left out
+        let patch = patch_text(&diff, 0, Some(1..=1), false).unwrap();
+        assert!(patch.ends_with("@@ -1,4 +1,3 @@ ctx\n 1\n-2\n 3\n 4\n"));
+
+        let patch = patch_text(&diff, 0, Some(3..=3), false).unwrap();
+        assert!(patch.ends_with("@@ -1,4 +1,5 @@ ctx\n 1\n 2\n 3\n+two\n 4\n"));
+    }
+
+    #[test]
+    fn hunk_patch_unstages_selected_lines_in_reverse() {
+        let diff = two_line_change();
+
+        // Applied in reverse the index holds the new side, so unselected
+        // additions become context and unselected removals are dropped
+        let patch = patch_text(&diff, 0, Some(1..=1), true).unwrap();
+        assert!(patch.ends_with("@@ -1,5 +1,4 @@ ctx\n 1\n-2\n two\n three\n 4\n"));
+
+        assert_eq!(patch_text(&diff, 0, Some(0..=0), true), None);
+    }
+
+    #[test]
+    fn hunk_patch_drops_no_newline_marker_with_its_line() {
+        let diff = FileDiff::parse(
+            "\
+--- a/f
++++ b/f
+@@ -1 +1 @@
+-old
+\\ No newline at end of file
++new
+\\ No newline at end of file
+"
+            .as_bytes(),
+        )
+        .remove(0);
+
+        let patch = patch_text(&diff, 0, Some(0..=1), false).unwrap();
+
+        assert!(patch.ends_with("@@ -1,1 +1,0 @@\n-old\n\\ No newline at end of file\n"));
+    }
+
+    #[test]
+    fn hunk_patch_keeps_crlf_and_non_utf8_bytes() {
+        let diff =
+            FileDiff::parse(b"--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n caf\xe9\r\n-old\r\n+new\r\n")
+                .remove(0);
+
+        // Shown without the line ending and decoded as well as it goes
+        assert_eq!(diff.hunks[0].lines[0].content, "caf\u{fffd}");
+        assert_eq!(diff.hunks[0].lines[1].content, "old");
+
+        let patch = diff.hunk_patch(0, Some(1..=1), false).unwrap();
+        assert_eq!(
+            patch,
+            b"--- a/f\n+++ b/f\n@@ -1,2 +1,1 @@\n caf\xe9\r\n-old\r\n"
+        );
+    }
+
+    #[test]
+    fn fetch_result_parses_ref_updates() {
+        let stderr = "From github.com:user/repo\n   \
+             1a2b3c4..5d6e7f8  main       -> origin/main\n \
+             + 0a0a0a0...1b1b1b1 wip        -> origin/wip  (forced update)\n \
+             * [new branch]      feature    -> origin/feature\n \
+             - [deleted]         (none)     -> origin/old\n";
+
+        let result = FetchResult::parse(stderr);
+
+        assert_eq!(result.url.as_deref(), Some("github.com:user/repo"));
+        let kinds: Vec<RefUpdateKind> = result.updates.iter().map(|u| u.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RefUpdateKind::FastForward,
+                RefUpdateKind::Forced,
+                RefUpdateKind::New,
+                RefUpdateKind::Pruned,
+            ]
+        );
+        assert_eq!(result.updates[1].to, "origin/wip");
+        assert_eq!(result.updates[2].summary, "[new branch]");
+        assert!(result
+            .to_string()
+            .starts_with("Fetched github.com:user/repo: 1 new, 2 updated, 1 pruned"));
+        assert_eq!(FetchResult::parse("").to_string(), "Nothing new to fetch");
+    }
+
+    #[test]
+    fn pull_result_parses_fast_forward() {
+        let stdout = "Updating 1a2b3c4..5d6e7f8\nFast-forward\n \
+             src/main.rs | 2 +-\n \
+             1 file changed, 1 insertion(+), 1 deletion(-)\n";
+        let stderr = "From github.com:user/repo\n \
+             * branch            main       -> FETCH_HEAD\n";
+
+        let result = PullResult::parse(stdout, stderr);
+
+        assert_eq!(result.outcome, PullOutcome::FastForward);
+        assert_eq!(result.range.as_deref(), Some("1a2b3c4..5d6e7f8"));
+        assert!(result.fetch.updates.is_empty());
+        assert_eq!(
+            result.to_string(),
+            "Fast-forwarded 1a2b3c4..5d6e7f8: 1 file changed, 1 insertion(+), 1 deletion(-)"
+        );
+        assert_eq!(
+            PullResult::parse("Already up to date.\n", "").outcome,
+            PullOutcome::UpToDate
+        );
+    }
+
+    #[test]
+    fn branch_list_parses_for_each_ref() {
+        let output = "*\0refs/heads/main\0e867c5a\0origin/main\0Second commit\n \
+             \0refs/heads/feature\x001a2b3c4\0\0Work in progress\n \
+             \0refs/remotes/origin/HEAD\0e867c5a\0\0Second commit\n \
+             \0refs/remotes/origin/main\0e867c5a\0\0Second commit\n";
+
+        let branches = Branch::parse_list(output);
+
+        assert_eq!(branches.len(), 3);
+        assert!(branches[0].head);
+        assert_eq!(branches[0].upstream.as_deref(), Some("origin/main"));
+        assert!(!branches[1].head);
+        assert_eq!(branches[1].upstream, None);
+        assert_eq!(branches[1].subject, "Work in progress");
+        assert!(branches[2].remote);
+        assert_eq!(branches[2].name, "origin/main");
+        assert_eq!(branches[2].local_name(), "main");
+    }
+
+    #[test]
+    fn progress_parses_git_updates() {
+        let writing = Progress::parse("Writing objects:  45% (9/20), 1.2 MiB | 300 KiB/s").unwrap();
+        let counting = Progress::parse("remote: Enumerating objects: 5, done.        ").unwrap();
+
+        assert_eq!(writing.phase, "Writing objects");
+        assert_eq!((writing.current, writing.total), (9, Some(20)));
+        assert_eq!(writing.fraction(), Some(0.45));
+        assert!(!writing.remote && !writing.done);
+        assert_eq!(counting.phase, "Enumerating objects");
+        assert_eq!((counting.current, counting.total), (5, None));
+        assert!(counting.remote && counting.done);
+        assert_eq!(Progress::parse("remote: Total 3 (delta 0), reused 0"), None);
+        assert_eq!(Progress::parse("error: failed to push some refs"), None);
+    }
+
+    #[test]
+    fn job_streams_stderr_lines_then_finishes() {
+        let job = Job::spawn(
+            std::process::Command::new("sh")
+                .arg("-c")
+                .arg("printf 'Counting objects:  50%% (1/2)\rCounting objects: 100%% (2/2), done.\nTo remote\n' >&2; echo out"),
+        )
+        .unwrap();
+
+        let mut lines = vec![];
+        let mut updates = vec![];
+        let output = loop {
+            match job.poll() {
+                Some(JobEvent::Line(line)) => lines.push(line),
+                Some(JobEvent::Progress(progress)) => updates.push(progress.current),
+                Some(JobEvent::Done(result)) => break result.unwrap(),
+                None => std::thread::sleep(std::time::Duration::from_millis(5)),
+            }
+        };
+
+        assert_eq!(lines, vec!["To remote"]);
+        assert_eq!(updates, vec![1, 2]);
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"To remote\n");
+    }
 
-        assert_eq!(mode.handle_key(27), Action::Exit);
+    #[test]
+    fn cancelled_job_gets_to_clean_up() {
+        let job = Job::spawn(
+            std::process::Command::new("sh")
+                .arg("-c")
+                .arg("trap 'echo cleaned up >&2; exit 3' TERM; while :; do sleep 0.05; done"),
+        )
+        .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        job.cancel();
+        let err = loop {
+            match job.poll() {
+                Some(JobEvent::Done(result)) => break result.unwrap_err(),
+                _ => std::thread::sleep(std::time::Duration::from_millis(5)),
+            }
+        };
+
+        // Killed it would have no exit code
+        assert_eq!(err.status, Some(3));
+        assert_eq!(err.stderr, "cleaned up");
+    }
+
+    #[test]
+    fn config_merges_bindings_over_defaults() {
+        let mut config = Config::new();
+        let text = "\
+# Emacs style movement
+[stage]
+\"<C-n>\" = \"CursorDown\"
+j = 'StageFile'   # rebinds j
+
+[push]
+\"-F\" = \"TogglePushForceWithLease\"
+\"=\" = \"TogglePushTags\"
+'a=b' = \"Exit\"
+";
+
+        let errors = config.apply(text);
+
+        assert_eq!(errors, vec![]);
+        let stage = &config.stage_mode_key_map;
+        assert!(stage.contains(&(String::from("<C-n>"), Action::CursorDown)));
+        assert!(stage.contains(&(String::from("j"), Action::StageFile)));
+        assert!(stage.contains(&(String::from("k"), Action::CursorUp)));
+        assert_eq!(stage.iter().filter(|(c, _)| c == "j").count(), 1);
+        assert!(config
+            .push_mode_key_map
+            .contains(&(String::from("-F"), Action::TogglePushForceWithLease)));
+        // `=` inside the quoted chord isn't the one separating the action
+        assert!(config
+            .push_mode_key_map
+            .contains(&(String::from("="), Action::TogglePushTags)));
+        assert!(config
+            .push_mode_key_map
+            .contains(&(String::from("a=b"), Action::Exit)));
+    }
+
+    #[test]
+    fn config_reports_errors_with_line_numbers() {
+        let mut config = Config::new();
+        let text = "\
+x = \"Discard\"
+[stage]
+j = \"MoveDown\"
+\"<Foo>\" = \"CursorDown\"
+? = \"OpenHelpMode\"
+k = CursorUp
+[nonsense]
+q = \"Exit\"
+[commit
+";
+
+        let lines: Vec<usize> = config.apply(text).iter().map(|e| e.line).collect();
+
+        assert_eq!(lines, vec![1, 3, 4, 5, 6, 7, 9]);
+        assert!(config
+            .stage_mode_key_map
+            .contains(&(String::from("j"), Action::CursorDown)));
+    }
+
+    #[test]
+    fn color_parses_and_downgrades() {
+        assert_eq!(Color::parse("default"), Ok(Color::Default));
+        assert_eq!(Color::parse("blue"), Ok(Color::Indexed(4)));
+        assert_eq!(Color::parse("bright-blue"), Ok(Color::Indexed(12)));
+        assert_eq!(Color::parse("153"), Ok(Color::Indexed(153)));
+        assert_eq!(Color::parse("#005f87"), Ok(Color::Rgb(0, 0x5f, 0x87)));
+        assert!(Color::parse("#05f87").is_err());
+        assert!(Color::parse("256").is_err());
+
+        // #005f87 is entry 24 of the 256 color cube, the closest basic
+        // color to it is cyan
+        assert_eq!(Color::Rgb(0, 0x5f, 0x87).to_curses(256), 24);
+        assert_eq!(Color::Indexed(24).to_curses(256), 24);
+        assert_eq!(Color::Indexed(24).to_curses(8), 6);
+        assert_eq!(Color::Indexed(196).to_curses(16), 9);
+        assert_eq!(Color::Indexed(196).to_curses(8), 1);
+        assert_eq!(Color::Indexed(4).to_curses(0), -1);
+        assert_eq!(Color::Default.to_curses(256), -1);
+    }
+
+    #[test]
+    fn config_sets_theme_colors() {
+        let mut config = Config::new();
+        let text = "\
+[theme]
+header = \"red\"
+base = \"light\"
+selected = \"black on #ffffff\"
+diff-add = \"bright-green\"
+nonsense = \"red\"
+diff-del = \"reddish\"
+";
+
+        let lines: Vec<usize> = config.apply(text).iter().map(|e| e.line).collect();
+
+        assert_eq!(lines, vec![6, 7]);
+        let theme = &config.theme;
+        // Replaced by the light theme
+        assert_eq!(
+            theme.style(Role::Header),
+            Theme::light().style(Role::Header)
+        );
+        assert_eq!(
+            theme.style(Role::Selected),
+            Style::new(Color::Indexed(0), Color::Rgb(255, 255, 255))
+        );
+        assert_eq!(theme.style(Role::DiffAdd).fg, Color::Indexed(10));
+        assert_eq!(
+            theme.style(Role::DiffDel),
+            Theme::light().style(Role::DiffDel)
+        );
+    }
+
+    #[test]
+    fn text_buffer_edits_across_lines() {
+        let mut buffer = TextBuffer::new();
+        for c in "Fix bug".chars() {
+            buffer.insert(c);
+        }
+        buffer.newline();
+        buffer.newline();
+        for c in "Körper".chars() {
+            buffer.insert(c);
+        }
+        assert_eq!(buffer.text(), "Fix bug\n\nKörper");
+        assert_eq!(buffer.cursor(), (2, 6));
+
+        buffer.left();
+        buffer.left();
+        buffer.left();
+        buffer.left();
+        buffer.backspace();
+        assert_eq!(buffer.text(), "Fix bug\n\nKrper");
+
+        buffer.up();
+        buffer.backspace();
+        assert_eq!(buffer.lines(), ["Fix bug", "Krper"]);
+        assert_eq!(buffer.cursor(), (0, 7));
+
+        buffer.delete();
+        assert_eq!(buffer.text(), "Fix bugKrper");
+    }
+
+    #[test]
+    fn text_buffer_moves_by_words() {
+        let mut buffer = TextBuffer::new();
+        for c in "let x_y = 1;".chars() {
+            buffer.insert(c);
+        }
+        buffer.newline();
+        for c in "  next".chars() {
+            buffer.insert(c);
+        }
+
+        buffer.word_backward();
+        assert_eq!(buffer.cursor(), (1, 2));
+        buffer.word_backward();
+        assert_eq!(buffer.cursor(), (0, 10));
+        buffer.word_backward();
+        assert_eq!(buffer.cursor(), (0, 4));
+
+        buffer.word_forward();
+        assert_eq!(buffer.cursor(), (0, 7));
+        buffer.word_forward();
+        assert_eq!(buffer.cursor(), (0, 11));
+        buffer.word_forward();
+        assert_eq!(buffer.cursor(), (1, 6));
+    }
+
+    #[test]
+    fn wrap_breaks_after_spaces() {
+        assert_eq!(wrap("short", 10), vec![0]);
+        assert_eq!(wrap("one two three four", 10), vec![0, 8]);
+        assert_eq!(wrap("abcdefghijklmnop", 6), vec![0, 6, 12]);
+    }
+
+    #[test]
+    fn wrap_counts_wide_characters_twice() {
+        assert_eq!(wrap("日本語 テキスト", 8), vec![0, 4]);
+        assert_eq!(wrap("日本語テキスト", 5), vec![0, 2, 4, 6]);
+    }
+
+    #[test]
+    fn clip_cuts_by_display_width() {
+        assert_eq!(display_width("aä日e\u{301}"), 5);
+        assert_eq!(clip("abcdef", 2, 3), "cde");
+        assert_eq!(clip("日本語", 0, 3), "日 ");
+        assert_eq!(clip("日本語", 1, 4), " 本 ");
+        assert_eq!(clip("e\u{301}x", 0, 1), "e\u{301}");
+        assert_eq!(clip("e\u{301}x", 1, 1), "x");
+    }
+
+    #[test]
+    fn utf8_len_reads_lead_bytes() {
+        assert_eq!(utf8_len(b'a'), Some(1));
+        assert_eq!("ä".bytes().next().and_then(utf8_len), Some(2));
+        assert_eq!("日".bytes().next().and_then(utf8_len), Some(3));
+        assert_eq!("🦀".bytes().next().and_then(utf8_len), Some(4));
+        assert_eq!(utf8_len(0x80), None);
+    }
+
+    #[test]
+    fn exclusive_args_keep_only_the_one_just_enabled() {
+        let args: &[PopupArg] = &[
+            (Action::ToggleResetSoft, "Soft", "--soft"),
+            (Action::ToggleResetHard, "Hard", "--hard"),
+        ];
+        let mut enabled = HashSet::new();
+
+        assert!(toggle_exclusive_arg(
+            &mut enabled,
+            args,
+            Action::ToggleResetSoft
+        ));
+        assert_eq!(enabled_args(args, &enabled), ["--soft"]);
+        assert!(toggle_exclusive_arg(
+            &mut enabled,
+            args,
+            Action::ToggleResetHard
+        ));
+        assert_eq!(enabled_args(args, &enabled), ["--hard"]);
+        // Toggling the enabled one leaves none
+        assert!(toggle_exclusive_arg(
+            &mut enabled,
+            args,
+            Action::ToggleResetHard
+        ));
+        assert!(enabled.is_empty());
+
+        assert!(!toggle_exclusive_arg(&mut enabled, args, Action::Exit));
+        assert!(enabled.is_empty());
+    }
+
+    fn item(section: Section, path: &str, row: ListRow) -> Item {
+        Item {
+            section,
+            path: PathBuf::from(path),
+            row,
+            y: 0,
+        }
+    }
+
+    #[test]
+    fn section_jump_goes_to_first_item_of_next_or_previous_section() {
+        let items = vec![
+            item(Section::Untracked, "new", ListRow::File(0)),
+            item(Section::Staged, "a", ListRow::File(0)),
+            item(Section::Staged, "b", ListRow::File(1)),
+            item(Section::Unstaged, "c", ListRow::File(0)),
+            item(Section::Unstaged, "c", ListRow::Hunk(0, 0)),
+        ];
+
+        assert_eq!(section_jump(&items, 2, true), Some(3));
+        assert_eq!(section_jump(&items, 2, false), Some(0));
+        assert_eq!(section_jump(&items, 1, false), Some(0));
+        // Nothing past the last or before the first section
+        assert_eq!(section_jump(&items, 4, true), None);
+        assert_eq!(section_jump(&items, 0, false), None);
+        assert_eq!(section_jump(&[], 0, true), None);
+    }
+
+    #[test]
+    fn item_at_stops_at_the_last_item() {
+        let items = vec![
+            item(Section::Staged, "a", ListRow::File(0)),
+            item(Section::Staged, "b", ListRow::File(1)),
+        ];
+
+        assert_eq!(item_at(&items, 0), Some(&items[0]));
+        assert_eq!(item_at(&items, 5), Some(&items[1]));
+        assert_eq!(item_at(&[], 0), None);
+    }
+
+    #[test]
+    fn find_cursor_keeps_the_same_path_after_a_refresh() {
+        let old = vec![
+            item(Section::Unstaged, "b", ListRow::File(0)),
+            item(Section::Unstaged, "b", ListRow::Hunk(0, 1)),
+            item(Section::Unstaged, "b", ListRow::Line(0, 1, 2)),
+        ];
+        // A file was modified before it, its index changed
+        let items = vec![
+            item(Section::Unstaged, "a", ListRow::File(0)),
+            item(Section::Unstaged, "b", ListRow::File(1)),
+            item(Section::Unstaged, "b", ListRow::Hunk(1, 1)),
+            item(Section::Unstaged, "b", ListRow::Line(1, 1, 2)),
+        ];
+
+        assert_eq!(find_cursor(&items, &old, &old[2]), Some(&items[3]));
+        assert_eq!(find_cursor(&items, &old, &old[1]), Some(&items[2]));
+
+        // The hunk is gone but the file is still there
+        let collapsed = &items[..2];
+        assert_eq!(find_cursor(collapsed, &old, &old[2]), Some(&items[1]));
+    }
+
+    #[test]
+    fn find_cursor_falls_back_to_the_closest_item_left() {
+        let old = vec![
+            item(Section::Unstaged, "a", ListRow::File(0)),
+            item(Section::Unstaged, "b", ListRow::File(1)),
+            item(Section::Unstaged, "c", ListRow::File(2)),
+        ];
+        // b was staged
+        let items = vec![
+            item(Section::Staged, "b", ListRow::File(0)),
+            item(Section::Unstaged, "a", ListRow::File(0)),
+            item(Section::Unstaged, "c", ListRow::File(1)),
+        ];
+
+        assert_eq!(find_cursor(&items, &old, &old[1]), Some(&items[2]));
+        // Without one after it, the one before it
+        assert_eq!(find_cursor(&items[..2], &old, &old[1]), Some(&items[1]));
+        assert_eq!(find_cursor(&items[..1], &old, &old[1]), None);
+    }
+
+    #[test]
+    fn follow_scrolls_just_enough_to_show_the_row() {
+        // Already in view
+        assert_eq!(follow(10, 15, 10, 100), 10);
+        // Above the view, it becomes the first row
+        assert_eq!(follow(10, 4, 10, 100), 4);
+        // Below the view, it becomes the last row
+        assert_eq!(follow(10, 25, 10, 100), 16);
+        // Content shorter than the view never scrolls
+        assert_eq!(follow(3, 4, 10, 6), 0);
+        // The view grew, it doesn't stay scrolled past the end
+        assert_eq!(follow(90, 95, 20, 100), 80);
+        // The view shrank, the row stays in it
+        assert_eq!(follow(10, 25, 5, 100), 21);
+        assert_eq!(follow(10, 12, 0, 100), 12);
+    }
+
+    #[test]
+    fn text_buffer_steps_over_combining_characters() {
+        let mut buffer = TextBuffer::new();
+        for c in "ae\u{301}b".chars() {
+            buffer.insert(c);
+        }
+        buffer.left();
+        buffer.left();
+        assert_eq!(buffer.cursor(), (0, 1));
+        buffer.right();
+        assert_eq!(buffer.cursor(), (0, 3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn repo_status_keeps_paths_that_arent_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let output = b"? caf\xe9.txt\0";
+
+        let status = RepoStatus::parse(output);
+
+        assert_eq!(
+            status.entries[0].path,
+            PathBuf::from(OsStr::from_bytes(b"caf\xe9.txt"))
+        );
+    }
+
+    #[test]
+    fn log_lines_split_graph_from_commits() {
+        let output =
+            "* \x1fabc1234\x1fAnna\x1f2024-01-02\x1fHEAD -> refs/heads/main\x1fFix the thing\n\
+                      |\\\n\
+                      | * \x1fdef5678\x1fBo\x1f2024-01-01\x1f\x1f\n";
+
+        assert_eq!(
+            LogLine::parse_list(output),
+            vec![
+                LogLine {
+                    graph: String::from("* "),
+                    commit: Some(Commit {
+                        oid: String::from("abc1234"),
+                        author: String::from("Anna"),
+                        date: String::from("2024-01-02"),
+                        refs: vec![
+                            Ref {
+                                kind: RefKind::Head,
+                                name: String::from("HEAD"),
+                            },
+                            Ref {
+                                kind: RefKind::Local,
+                                name: String::from("main"),
+                            },
+                        ],
+                        subject: String::from("Fix the thing"),
+                    }),
+                },
+                LogLine {
+                    graph: String::from("|\\"),
+                    commit: None,
+                },
+                LogLine {
+                    graph: String::from("| * "),
+                    commit: Some(Commit {
+                        oid: String::from("def5678"),
+                        author: String::from("Bo"),
+                        date: String::from("2024-01-01"),
+                        refs: vec![],
+                        subject: String::new(),
+                    }),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn refs_parse_by_kind() {
+        let refs =
+            Ref::parse_list("HEAD, refs/remotes/origin/main, tag: refs/tags/v1.0, refs/stash");
+
+        assert_eq!(
+            refs.iter()
+                .map(|r| (r.kind, r.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (RefKind::Head, "HEAD"),
+                (RefKind::Remote, "origin/main"),
+                (RefKind::Tag, "v1.0"),
+                (RefKind::Other, "refs/stash"),
+            ]
+        );
+    }
+
+    #[test]
+    fn commit_details_parse_message_and_diff() {
+        let output =
+            "abc1234def\x1fAnna <anna@example.com>\x1f2024-01-02\x1fSummary\n\nBody\n\n\0\n\
+                      diff --git a/src/a.rs b/src/a.rs\n\
+                      index 1111111..2222222 100644\n\
+                      --- a/src/a.rs\n\
+                      +++ b/src/a.rs\n\
+                      @@ -1 +1 @@\n\
+                      -old\n\
+                      +new\n\
+                      diff --git a/gone.txt b/gone.txt\n\
+                      deleted file mode 100644\n\
+                      index 3333333..0000000\n\
+                      --- a/gone.txt\n\
+                      +++ /dev/null\n\
+                      @@ -1 +0,0 @@\n\
+                      -bye\n";
+
+        let details = CommitDetails::parse(output).unwrap();
+
+        assert_eq!(details.oid, "abc1234def");
+        assert_eq!(details.author, "Anna <anna@example.com>");
+        assert_eq!(details.date, "2024-01-02");
+        assert_eq!(details.message, "Summary\n\nBody");
+        assert_eq!(
+            details.diffs.iter().map(|d| d.path()).collect::<Vec<_>>(),
+            vec![Some("src/a.rs"), Some("gone.txt")]
+        );
+        assert!(CommitDetails::parse("no separator").is_none());
+    }
+
+    #[test]
+    fn file_diff_path_falls_back_to_diff_line() {
+        let diff = FileDiff {
+            header: vec![
+                String::from("diff --git a/empty.txt b/empty.txt"),
+                String::from("new file mode 100644"),
+            ],
+            ..FileDiff::default()
+        };
+
+        assert_eq!(diff.path(), Some("empty.txt"));
+    }
+
+    #[test]
+    fn stashes_parse_name_and_message() {
+        let output =
+            "stash@{0}\0On main: half done\nstash@{1}\0WIP on main: abc1234 Subject\nbroken\n";
+
+        assert_eq!(
+            Stash::parse_list(output),
+            vec![
+                Stash {
+                    name: String::from("stash@{0}"),
+                    message: String::from("On main: half done"),
+                },
+                Stash {
+                    name: String::from("stash@{1}"),
+                    message: String::from("WIP on main: abc1234 Subject"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn todo_list_round_trips_through_git_format() {
+        let mut todo = TodoLine::parse_list("abc1234\0First\ndef5678\0Second: more\nbroken\n");
+        assert_eq!(todo.len(), 2);
+        assert!(todo.iter().all(|l| l.verb == TodoVerb::Pick));
+
+        todo[1].verb = TodoVerb::Fixup;
+        todo.swap(0, 1);
+        assert_eq!(
+            TodoLine::format_list(&todo),
+            "fixup def5678 Second: more\npick abc1234 First\n"
+        );
+        assert!(todo[0].verb.melds() && !todo[1].verb.melds());
+    }
+
+    #[test]
+    fn sequencer_todo_parses_picks_and_reverts() {
+        let todo = SequencerLine::parse_list(
+            "pick ba0d18e First\nrevert f3863e2 Revert \"Second\"\n# comment\nexec make\n",
+        );
+
+        assert_eq!(
+            todo,
+            [
+                SequencerLine {
+                    op: Operation::CherryPick,
+                    oid: String::from("ba0d18e"),
+                    subject: String::from("First"),
+                },
+                SequencerLine {
+                    op: Operation::Revert,
+                    oid: String::from("f3863e2"),
+                    subject: String::from("Revert \"Second\""),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn text_buffer_set_text_splits_lines() {
+        let mut buffer = TextBuffer::new();
+        buffer.insert('x');
+        buffer.set_text("Summary\n\nBody");
+
+        assert_eq!(buffer.lines(), ["Summary", "", "Body"]);
+        assert_eq!(buffer.cursor(), (0, 0));
+        assert_eq!(buffer.text(), "Summary\n\nBody");
+    }
+
+    #[test]
+    fn unbound_popup_arguments_keep_their_own_enabled_state() {
+        let mut args = ArgList::new();
+        args.push_arg("", "Force with lease", "--force-with-lease", false);
+        args.push_arg("", "Dry run", "--dry-run", true);
+        assert_eq!(args.get_enabled(), ["--dry-run"]);
     }
 }
@@ -0,0 +1,297 @@
+use crate::win::*;
+
+use std::collections::HashMap;
+
+/// A color as written in a theme
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    /// The terminal's own foreground or background
+    Default,
+    /// An entry of the terminal's palette. 0-7 are the basic colors, 8-15
+    /// their bright variants and 16-255 the xterm color cube and grays
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+const COLOR_NAMES: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// What xterm shows the first 16 palette entries as, used to find the
+/// closest of them when a terminal has no more than that
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+impl Color {
+    /// Parses `default`, a color name like `blue` or `bright-blue`, a
+    /// palette index from 0 to 255 or `#rrggbb`.
+    pub fn parse(s: &str) -> Result<Color, String> {
+        let s = s.trim();
+        if s == "default" {
+            return Ok(Color::Default);
+        }
+        if let Some(i) = COLOR_NAMES.iter().position(|n| *n == s) {
+            return Ok(Color::Indexed(i as u8));
+        }
+        if let Some(i) = s
+            .strip_prefix("bright-")
+            .and_then(|s| COLOR_NAMES.iter().position(|n| *n == s))
+        {
+            return Ok(Color::Indexed(i as u8 + 8));
+        }
+        if let Some(hex) = s.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+                _ => Err(format!("\"{}\" isn't a color like #rrggbb", s)),
+            };
+        }
+        s.parse()
+            .map(Color::Indexed)
+            .map_err(|_| format!("unknown color \"{}\"", s))
+    }
+
+    /// The curses color to show this as on a terminal with `colors` colors.
+    /// Colors the terminal doesn't have become the closest one it does.
+    pub fn to_curses(self, colors: i32) -> i16 {
+        match self {
+            Color::Default => -1,
+            _ if colors < 8 => -1,
+            Color::Indexed(i) if (i as i32) < colors => i as i16,
+            Color::Indexed(i) => closest(rgb_of(i), colors),
+            Color::Rgb(r, g, b) => closest((r, g, b), colors),
+        }
+    }
+}
+
+/// What xterm shows palette entry `i` as
+fn rgb_of(i: u8) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match i {
+        0..=15 => BASIC_RGB[i as usize],
+        16..=231 => {
+            let i = i - 16;
+            (
+                LEVELS[(i / 36) as usize],
+                LEVELS[(i / 6 % 6) as usize],
+                LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let gray = 8 + 10 * (i - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// The palette entry closest to `rgb`. Terminals with 256 colors get one
+/// of the cube or grays, those don't depend on the terminal's color scheme.
+fn closest(rgb: (u8, u8, u8), colors: i32) -> i16 {
+    let candidates = if colors >= 256 {
+        16..=255
+    } else {
+        0..=(colors.min(16) - 1) as u8
+    };
+    let distance = |i: &u8| {
+        let (r, g, b) = rgb_of(*i);
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+    };
+    candidates.min_by_key(distance).unwrap_or(0) as i16
+}
+
+/// Foreground and background of a role
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Style {
+    pub fn new(fg: Color, bg: Color) -> Style {
+        Style { fg, bg }
+    }
+
+    /// Parses `fg` or `fg on bg`
+    pub fn parse(s: &str) -> Result<Style, String> {
+        match s.split_once(" on ") {
+            Some((fg, bg)) => Ok(Style::new(Color::parse(fg)?, Color::parse(bg)?)),
+            None => Ok(Style::new(Color::parse(s)?, Color::Default)),
+        }
+    }
+}
+
+/// What a color pair is used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    Default,
+    /// Notifications and text that was typed in
+    Info,
+    Error,
+    Header,
+    Selected,
+    Untracked,
+    Staged,
+    Unstaged,
+    Separator,
+    /// Popup arguments that are switched on
+    Enabled,
+    DiffAdd,
+    DiffDel,
+    Branch,
+    RemoteBranch,
+}
+
+impl Role {
+    pub const ALL: &'static [Role] = &[
+        Role::Default,
+        Role::Info,
+        Role::Error,
+        Role::Header,
+        Role::Selected,
+        Role::Untracked,
+        Role::Staged,
+        Role::Unstaged,
+        Role::Separator,
+        Role::Enabled,
+        Role::DiffAdd,
+        Role::DiffDel,
+        Role::Branch,
+        Role::RemoteBranch,
+    ];
+
+    /// The name used in the `[theme]` table of the config
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Default => "default",
+            Role::Info => "info",
+            Role::Error => "error",
+            Role::Header => "header",
+            Role::Selected => "selected",
+            Role::Untracked => "untracked",
+            Role::Staged => "staged",
+            Role::Unstaged => "unstaged",
+            Role::Separator => "separator",
+            Role::Enabled => "enabled",
+            Role::DiffAdd => "diff-add",
+            Role::DiffDel => "diff-del",
+            Role::Branch => "branch",
+            Role::RemoteBranch => "remote-branch",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Role> {
+        Role::ALL.iter().copied().find(|r| r.name() == name)
+    }
+
+    /// The color pair drawn with
+    pub fn pair(self) -> u32 {
+        match self {
+            Role::Default => COLOR_PAIR_DEFAULT,
+            Role::Info => COLOR_PAIR_H1,
+            Role::Error => COLOR_PAIR_H2,
+            Role::Header => COLOR_PAIR_H3,
+            Role::Selected => COLOR_PAIR_SELECTED,
+            Role::Untracked => COLOR_PAIR_UNTRACKED,
+            Role::Staged => COLOR_PAIR_STAGED,
+            Role::Unstaged => COLOR_PAIR_UNSTAGED,
+            Role::Separator => COLOR_PAIR_SEP,
+            Role::Enabled => COLOR_PAIR_ENABLED,
+            Role::DiffAdd => COLOR_PAIR_ADDED,
+            Role::DiffDel => COLOR_PAIR_REMOVED,
+            Role::Branch => COLOR_PAIR_LOCAL,
+            Role::RemoteBranch => COLOR_PAIR_REMOTE,
+        }
+    }
+}
+
+/// Names of the themes that come with the program, see `Theme::bundled`
+pub const BUNDLED_THEMES: &[&str] = &["dark", "light"];
+
+pub struct Theme {
+    styles: HashMap<Role, Style>,
+}
+
+impl Theme {
+    /// The theme for terminals with a dark background, used unless the
+    /// config says otherwise
+    pub fn dark() -> Theme {
+        use Color::*;
+        Theme::from_styles(&[
+            (Role::Info, Style::new(Indexed(2), Default)),
+            (Role::Error, Style::new(Indexed(1), Default)),
+            (Role::Header, Style::new(Indexed(4), Default)),
+            (Role::Selected, Style::new(Indexed(0), Indexed(7))),
+            (Role::Untracked, Style::new(Indexed(5), Default)),
+            (Role::Separator, Style::new(Indexed(0), Indexed(4))),
+            (Role::Enabled, Style::new(Indexed(3), Default)),
+            (Role::DiffAdd, Style::new(Indexed(2), Default)),
+            (Role::DiffDel, Style::new(Indexed(1), Default)),
+            (Role::Branch, Style::new(Indexed(6), Default)),
+            (Role::RemoteBranch, Style::new(Indexed(2), Default)),
+        ])
+    }
+
+    /// Darker colors that stay readable on a light background
+    pub fn light() -> Theme {
+        use Color::*;
+        Theme::from_styles(&[
+            (Role::Info, Style::new(Indexed(22), Default)),
+            (Role::Error, Style::new(Indexed(124), Default)),
+            (Role::Header, Style::new(Indexed(25), Default)),
+            (Role::Selected, Style::new(Indexed(255), Indexed(24))),
+            (Role::Untracked, Style::new(Indexed(90), Default)),
+            (Role::Separator, Style::new(Indexed(255), Indexed(25))),
+            (Role::Enabled, Style::new(Indexed(130), Default)),
+            (Role::DiffAdd, Style::new(Indexed(28), Default)),
+            (Role::DiffDel, Style::new(Indexed(160), Default)),
+            (Role::Branch, Style::new(Indexed(30), Default)),
+            (Role::RemoteBranch, Style::new(Indexed(28), Default)),
+        ])
+    }
+
+    pub fn bundled(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            _ => None,
+        }
+    }
+
+    /// Roles missing from `styles` use the terminal's colors
+    fn from_styles(styles: &[(Role, Style)]) -> Theme {
+        Theme {
+            styles: styles.iter().copied().collect(),
+        }
+    }
+
+    pub fn style(&self, role: Role) -> Style {
+        self.styles
+            .get(&role)
+            .copied()
+            .unwrap_or_else(|| Style::new(Color::Default, Color::Default))
+    }
+
+    pub fn set(&mut self, role: Role, style: Style) {
+        self.styles.insert(role, style);
+    }
+}
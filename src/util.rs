@@ -1,15 +1,83 @@
-pub fn str_to_char_arr(s: &str) -> Vec<i32> {
-    let mut output = Vec::with_capacity(s.len());
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use std::path::PathBuf;
+
+/// Columns `s` takes up on the screen. Wide characters like CJK take two,
+/// combining ones none.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Columns `c` takes up on the screen, control characters count as none
+pub fn char_width(c: char) -> usize {
+    UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+/// The part of `s` shown in columns `skip` to `skip + width`. Wide
+/// characters cut in half by either edge are replaced by spaces so
+/// everything after them stays in its column.
+pub fn clip(s: &str, skip: usize, width: usize) -> String {
+    let mut visible = String::new();
+    let mut col = 0;
     for c in s.chars() {
-        output.push(c as u8 as i32);
+        let w = char_width(c);
+        let start = col;
+        col += w;
+        // Combining characters go with the one before, hidden if it is
+        if col <= skip {
+            continue;
+        }
+        if col > skip + width {
+            visible.extend((start.max(skip)..skip + width).map(|_| ' '));
+            break;
+        }
+        if start < skip {
+            visible.extend((skip..col).map(|_| ' '));
+        } else {
+            visible.push(c);
+        }
     }
-    output
+    visible
 }
 
-pub fn char_arr_to_str(arr: &Vec<i32>) -> String {
-    let mut output = String::new();
-    for c in arr {
-        output.push((*c) as u8 as char);
+/// The scroll offset closest to `scroll` that shows `row` in a view
+/// `height` rows high onto `rows` rows of content.
+pub fn follow(scroll: i32, row: i32, height: i32, rows: i32) -> i32 {
+    let height = height.max(1);
+    let scroll = if row < scroll {
+        row
+    } else if row >= scroll + height {
+        row - height + 1
+    } else {
+        scroll
+    };
+    scroll.clamp(0, (rows - height).max(0))
+}
+
+/// How many bytes the UTF-8 encoded character starting with `lead` has,
+/// `None` if `lead` can't start one
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn utf8_len(lead: u8) -> Option<usize> {
+    match lead {
+        0x00..=0x7f => Some(1),
+        0xc2..=0xdf => Some(2),
+        0xe0..=0xef => Some(3),
+        0xf0..=0xf4 => Some(4),
+        _ => None,
     }
-    output
+}
+
+/// A path git printed. Paths are bytes on Unix and don't have to be UTF-8.
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// A path git printed. Git for Windows prints them as UTF-8.
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
 }
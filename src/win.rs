@@ -1,14 +1,20 @@
 use bitflags::bitflags;
 use itertools::izip;
 use pancurses::{
-    self, cbreak, curs_set, endwin, init_pair, noecho, raw, start_color, use_default_colors,
-    Attribute, COLOR_BLACK, COLOR_BLUE, COLOR_GREEN, COLOR_MAGENTA, COLOR_PAIR, COLOR_RED,
-    COLOR_WHITE, COLOR_YELLOW,
+    self, cbreak, chtype, curs_set, endwin, has_colors, init_pair, noecho, raw, start_color,
+    use_default_colors, Attribute, Input, A_CHARTEXT, A_COLOR, COLORS, COLOR_PAIR,
 };
 
+use crate::git::{
+    Branch, Commit, FileDiff, LineKind, LogLine, Progress, RefKind, Stash, TodoLine, TodoVerb,
+};
+use crate::key::Key;
+use crate::theme::{Role, Theme};
+use crate::util::*;
+
+use std::collections::HashMap;
 use std::ops;
 use std::path::PathBuf;
-use std::ptr;
 
 bitflags! {
     pub struct TextStyle: u8 {
@@ -30,8 +36,14 @@ pub static COLOR_PAIR_SELECTED: u32 = 5;
 pub static COLOR_PAIR_UNTRACKED: u32 = 6;
 pub static COLOR_PAIR_SEP: u32 = 7;
 pub static COLOR_PAIR_ENABLED: u32 = 8;
+pub static COLOR_PAIR_ADDED: u32 = 9;
+pub static COLOR_PAIR_REMOVED: u32 = 10;
+pub static COLOR_PAIR_LOCAL: u32 = 11;
+pub static COLOR_PAIR_REMOTE: u32 = 12;
+pub static COLOR_PAIR_STAGED: u32 = 13;
+pub static COLOR_PAIR_UNSTAGED: u32 = 14;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Coord {
     pub x: i32,
     pub y: i32,
@@ -39,6 +51,8 @@ pub struct Coord {
 
 pub struct Window {
     pub win: pancurses::Window,
+    /// How long `read_key` waits for input in milliseconds, -1 waits forever
+    timeout: i32,
 }
 
 pub struct ArgList {
@@ -51,10 +65,63 @@ pub struct ArgList {
 
 pub struct FileList {
     pub files: Vec<PathBuf>,
+    /// Diffs shown below expanded files, keyed by index into `files`
+    pub diffs: HashMap<usize, DiffView>,
+    /// Shown in front of the files, like the kind of conflict they are in
+    pub labels: Vec<String>,
+    /// Shown after the files, like what changed in a submodule
+    pub notes: Vec<String>,
     pub style: TextStyle,
     pub c_pair: u32,
 }
 
+/// What a row of a `FileList` shows, as indices into the files, hunks and
+/// hunk lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListRow {
+    File(usize),
+    Hunk(usize, usize),
+    Line(usize, usize, usize),
+}
+
+pub struct DiffView {
+    pub diff: FileDiff,
+}
+
+/// Branches with the commit they point to, the checked out one marked
+pub struct BranchList {
+    pub branches: Vec<Branch>,
+}
+
+/// Commits as their abbreviated hash followed by the subject
+pub struct CommitList {
+    pub commits: Vec<Commit>,
+}
+
+/// Lines of `git log`, each commit with the graph drawn in front of it and
+/// its author and date at the right edge
+pub struct LogList {
+    pub lines: Vec<LogLine>,
+}
+
+/// The todo list of an interactive rebase, each commit after what is done
+/// with it
+pub struct TodoList {
+    pub lines: Vec<TodoLine>,
+}
+
+/// Stashes, each name followed by its message
+pub struct StashList {
+    pub stashes: Vec<Stash>,
+}
+
+/// What a running job is up to: a label followed by a bar filling up as
+/// git reports progress, or just the label if it hasn't.
+pub struct ProgressBar {
+    pub label: String,
+    pub progress: Option<Progress>,
+}
+
 pub struct KeyList {
     keys: Vec<String>,
     descs: Vec<String>,
@@ -87,7 +154,7 @@ pub trait UiElement {
 
 impl Coord {
     pub fn new(x: i32, y: i32) -> Coord {
-        Coord { x: x, y: y }
+        Coord { x, y }
     }
 }
 
@@ -112,13 +179,90 @@ impl ops::Sub for Coord {
 }
 
 impl Window {
+    /// How long to wait for the rest of a character once its first byte
+    /// has arrived, in milliseconds
+    #[cfg(target_os = "linux")]
+    const UTF8_TIMEOUT_MS: i32 = 50;
+
     pub fn new() -> Window {
         Window {
             win: pancurses::initscr(),
+            timeout: -1,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: i32) {
+        self.timeout = timeout;
+        self.win.timeout(timeout);
+    }
+
+    /// Waits for the next key press. `None` if the timeout ran out first or
+    /// the input wasn't a key.
+    pub fn read_key(&self) -> Option<Key> {
+        let key = Key::from_input(self.getch()?)?;
+        if key != Key::Esc {
+            return Some(key);
+        }
+
+        // Terminals send Alt chords as escape followed by the character,
+        // both at once. A lone escape is just that.
+        self.win.timeout(0);
+        let next = self.getch();
+        self.win.timeout(self.timeout);
+        match next {
+            Some(Input::Character(c)) if Key::from_char(c) == Key::Char(c) => Some(Key::Alt(c)),
+            Some(input) => {
+                // Not an Alt chord after all, it's read on the next call
+                self.win.ungetch(&input);
+                Some(Key::Esc)
+            }
+            None => Some(Key::Esc),
+        }
+    }
+
+    /// The next input from the terminal. On Linux characters are put
+    /// together from their UTF-8 bytes here, pancurses throws away what it
+    /// read of one if the rest doesn't arrive before the timeout.
+    #[cfg(target_os = "linux")]
+    fn getch(&self) -> Option<Input> {
+        use ncurses::{stdscr, ungetch, wgetch, wtimeout, ERR};
+
+        let first = match wgetch(stdscr()) {
+            ERR => return None,
+            c @ 0x00..=0x7f => return Some(Input::Character(c as u8 as char)),
+            c @ 0x80..=0xff => c as u8,
+            key => {
+                // A key curses has a code for, pancurses knows which
+                ungetch(key);
+                return self.win.getch();
+            }
+        };
+        // Bytes that can't start a character are dropped
+        let len = utf8_len(first)?;
+
+        let mut bytes = vec![first];
+        wtimeout(stdscr(), Window::UTF8_TIMEOUT_MS);
+        while bytes.len() < len {
+            match wgetch(stdscr()) {
+                c @ 0x80..=0xbf => bytes.push(c as u8),
+                ERR => break,
+                other => {
+                    ungetch(other);
+                    break;
+                }
+            }
         }
+        wtimeout(stdscr(), self.timeout);
+        let s = String::from_utf8(bytes).ok()?;
+        s.chars().next().map(Input::Character)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn getch(&self) -> Option<Input> {
+        self.win.getch()
     }
 
-    pub fn init(&mut self) {
+    pub fn init(&mut self, theme: &Theme) {
         self.win.keypad(true);
         noecho();
         curs_set(0);
@@ -127,14 +271,16 @@ impl Window {
 
         use_default_colors();
         start_color();
-        init_pair(COLOR_PAIR_DEFAULT as i16, -1, -1);
-        init_pair(COLOR_PAIR_H1 as i16, COLOR_GREEN, -1);
-        init_pair(COLOR_PAIR_H2 as i16, COLOR_RED, -1);
-        init_pair(COLOR_PAIR_H3 as i16, COLOR_BLUE, -1);
-        init_pair(COLOR_PAIR_SELECTED as i16, COLOR_BLACK, COLOR_WHITE);
-        init_pair(COLOR_PAIR_UNTRACKED as i16, COLOR_MAGENTA, -1);
-        init_pair(COLOR_PAIR_SEP as i16, COLOR_BLACK, COLOR_BLUE);
-        init_pair(COLOR_PAIR_ENABLED as i16, COLOR_YELLOW, -1);
+        // Colors the terminal can't show are swapped for the closest it can
+        let colors = if has_colors() { COLORS() } else { 0 };
+        for role in Role::ALL {
+            let style = theme.style(*role);
+            init_pair(
+                role.pair() as i16,
+                style.fg.to_curses(colors),
+                style.bg.to_curses(colors),
+            );
+        }
     }
 
     pub fn render(&self) {
@@ -145,17 +291,81 @@ impl Window {
         endwin();
     }
 
+    /// Hands the terminal back for a program that needs it, `resume` takes
+    /// it over again
+    pub fn suspend(&self) {
+        endwin();
+    }
+
+    pub fn resume(&self) {
+        self.win.refresh();
+    }
+
     pub fn get_size(&self) -> Coord {
         let (max_y, max_x) = self.win.get_max_yx();
         Coord::new(max_x, max_y)
     }
 }
 
+/// Draws `s` at row `y`, column `x` of `win` without wrapping. Whatever
+/// doesn't fit on the row is cut off and rows outside of `win` aren't drawn.
+/// Positions are in screen columns, see `display_width`.
+pub fn put_str<T: AsRef<str>>(win: &pancurses::Window, y: i32, x: i32, s: T) {
+    let (max_y, max_x) = win.get_max_yx();
+    if y < 0 || y >= max_y || x >= max_x {
+        return;
+    }
+
+    let (x, hidden) = if x < 0 { (0, -x as usize) } else { (x, 0) };
+    win.mvaddstr(y, x, clip(s.as_ref(), hidden, (max_x - x) as usize));
+}
+
+/// Gives the cell at row `y`, column `x` of `win` the attributes and
+/// color pair `restyle` makes of its current ones, keeping the character.
+/// Writing back what `mvinch` returned would mangle anything but ASCII.
+pub fn restyle_cell<F>(win: &pancurses::Window, y: i32, x: i32, restyle: F)
+where
+    F: FnOnce(chtype, u32) -> (chtype, u32),
+{
+    let cell = win.mvinch(y, x);
+    let pair = ((cell & A_COLOR) / COLOR_PAIR(1)) as u32;
+    let (attributes, pair) = restyle(cell & !A_CHARTEXT & !A_COLOR, pair);
+    win.mvchgat(y, x, 1, attributes, pair as i16);
+}
+
+impl ListRow {
+    pub fn file(&self) -> usize {
+        match *self {
+            ListRow::File(f) | ListRow::Hunk(f, _) | ListRow::Line(f, _, _) => f,
+        }
+    }
+}
+
 impl FileList {
     pub fn len(&self) -> usize {
         self.files.len()
     }
 
+    pub fn rows(&self) -> Vec<ListRow> {
+        let mut rows = vec![];
+        for i in 0..self.len() {
+            rows.push(ListRow::File(i));
+            if let Some(diff) = self.diffs.get(&i) {
+                rows.extend(diff.rows(i));
+            }
+        }
+        rows
+    }
+
+    /// Columns the labels take up, including the space after them
+    fn label_width(&self) -> i32 {
+        self.labels
+            .iter()
+            .map(|l| display_width(l) as i32 + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
     fn bold(&self) -> bool {
         self.style.intersects(TextStyle::BOLD)
     }
@@ -173,59 +383,147 @@ impl UiElement for FileList {
     fn new() -> FileList {
         FileList {
             files: vec![],
+            diffs: HashMap::new(),
+            labels: vec![],
+            notes: vec![],
             style: TextStyle::NORMAL,
             c_pair: COLOR_PAIR_DEFAULT,
         }
     }
 
     fn render(&self, win: &pancurses::Window, c: Coord) {
-        if self.bold() {
-            win.attron(Attribute::Bold);
+        let mut y = c.y;
+        let x = c.x + self.label_width();
+        for (i, path) in self.files.iter().enumerate() {
+            if let Some(label) = self.labels.get(i) {
+                put_str(win, y, c.x, label);
+            }
+            if self.bold() {
+                win.attron(Attribute::Bold);
+            }
+            if self.italic() {
+                win.attron(Attribute::Italic);
+            }
+            if self.underlined() {
+                win.attron(Attribute::Underline);
+            }
+            win.attron(COLOR_PAIR(self.c_pair));
+            put_str(win, y, x, path.to_string_lossy());
+            if self.bold() {
+                win.attroff(Attribute::Bold);
+            }
+            if self.italic() {
+                win.attroff(Attribute::Italic);
+            }
+            if self.underlined() {
+                win.attroff(Attribute::Underline);
+            }
+            win.attroff(COLOR_PAIR(self.c_pair));
+            match self.notes.get(i) {
+                Some(note) if !note.is_empty() => {
+                    let width = display_width(&path.to_string_lossy()) as i32;
+                    put_str(win, y, x + width + 1, format!("({})", note));
+                }
+                _ => {}
+            }
+            y += 1;
+
+            if let Some(diff) = self.diffs.get(&i) {
+                diff.render(win, Coord::new(c.x, y));
+                y += diff.size().y;
+            }
         }
-        if self.italic() {
-            win.attron(Attribute::Italic);
+    }
+
+    fn size(&self) -> Coord {
+        let mut biggest = 0;
+        let mut l;
+        for (i, p) in self.files.iter().enumerate() {
+            l = display_width(&p.to_string_lossy()) as i32;
+            match self.notes.get(i) {
+                Some(note) if !note.is_empty() => l += display_width(note) as i32 + 3,
+                _ => {}
+            }
+            if l > biggest {
+                biggest = l;
+            }
         }
-        if self.underlined() {
-            win.attron(Attribute::Underline);
+        biggest += self.label_width();
+        for d in self.diffs.values() {
+            biggest = biggest.max(d.size().x);
         }
-        win.attron(COLOR_PAIR(self.c_pair.into()));
-        let mut i = 0;
-        for path in &self.files {
-            win.mvaddstr(c.y + i, c.x, &format!("{}\n", path.to_str().unwrap()));
-            i += 1;
+        Coord::new(biggest, self.rows().len() as i32)
+    }
+}
+
+impl DiffView {
+    pub fn rows(&self, file: usize) -> Vec<ListRow> {
+        if self.diff.is_binary() {
+            return vec![ListRow::File(file)];
         }
-        if self.bold() {
-            win.attroff(Attribute::Bold);
+
+        let mut rows = vec![];
+        for (h, hunk) in self.diff.hunks.iter().enumerate() {
+            rows.push(ListRow::Hunk(file, h));
+            rows.extend((0..hunk.lines.len()).map(|l| ListRow::Line(file, h, l)));
         }
-        if self.italic() {
-            win.attroff(Attribute::Italic);
+        rows
+    }
+}
+
+impl UiElement for DiffView {
+    fn new() -> DiffView {
+        DiffView {
+            diff: FileDiff::default(),
         }
-        if self.underlined() {
-            win.attroff(Attribute::Underline);
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        if self.diff.is_binary() {
+            put_str(win, c.y, c.x, "Binary file");
+            return;
+        }
+
+        let mut y = c.y;
+        for hunk in &self.diff.hunks {
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, c.x, hunk.header());
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+            y += 1;
+
+            for line in &hunk.lines {
+                let c_pair = match line.kind {
+                    LineKind::Added => COLOR_PAIR_ADDED,
+                    LineKind::Removed => COLOR_PAIR_REMOVED,
+                    LineKind::Context | LineKind::NoNewline => COLOR_PAIR_DEFAULT,
+                };
+                win.attron(COLOR_PAIR(c_pair));
+                put_str(win, y, c.x, format!("{}{}", line.prefix(), line.content));
+                win.attroff(COLOR_PAIR(c_pair));
+                y += 1;
+            }
         }
-        win.attroff(COLOR_PAIR(self.c_pair.into()));
     }
 
     fn size(&self) -> Coord {
-        let mut biggest = 0;
-        let mut l;
-        for p in &self.files {
-            l = p.to_str().unwrap().len();
-            if l > biggest {
-                biggest = l;
+        let mut max_width = 0;
+        for hunk in &self.diff.hunks {
+            max_width = max_width.max(display_width(&hunk.header()));
+            for line in &hunk.lines {
+                max_width = max_width.max(display_width(&line.content) + 1);
             }
         }
-        Coord::new(biggest as i32, self.len() as i32)
+        Coord::new(max_width as i32, self.rows(0).len() as i32)
     }
 }
 
 impl ArgList {
-    pub fn push_arg(&mut self, arg: &str, arg_desc: &str, arg_long: &str) {
+    pub fn push_arg(&mut self, arg: &str, arg_desc: &str, arg_long: &str, enabled: bool) {
         self.args.push(String::from(arg));
         self.arg_descs.push(String::from(arg_desc));
         self.arg_long.push(String::from(arg_long));
 
-        self.enabled.push(false);
+        self.enabled.push(enabled);
     }
 
     #[allow(dead_code)]
@@ -237,11 +535,6 @@ impl ArgList {
             .map(|(_, a)| String::from(a))
             .collect()
     }
-
-    pub fn toggle(&mut self, arg: &str) {
-        let index = self.args.iter().position(|x| arg == *x).unwrap();
-        self.enabled[index] = !self.enabled[index];
-    }
 }
 
 impl UiElement for ArgList {
@@ -262,39 +555,34 @@ impl UiElement for ArgList {
             &self.arg_long,
             &self.enabled
         ) {
-            win.attron(COLOR_PAIR(COLOR_PAIR_UNTRACKED.into()));
+            win.attron(COLOR_PAIR(COLOR_PAIR_UNTRACKED));
             if *e {
                 win.attron(Attribute::Bold);
             }
-            win.mvaddstr(c.y + i as i32, c.x, arg);
-            win.attroff(COLOR_PAIR(COLOR_PAIR_UNTRACKED.into()));
+            put_str(win, c.y + i as i32, c.x, arg);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_UNTRACKED));
             if *e {
                 win.attroff(Attribute::Bold);
             }
-            win.mvaddstr(c.y + i as i32, c.x + arg.len() as i32 + 1, arg_d);
+            let desc_x = c.x + display_width(arg) as i32 + 1;
+            put_str(win, c.y + i as i32, desc_x, arg_d);
+            let long_x = desc_x + display_width(arg_d) as i32 + 1;
+            put_str(win, c.y + i as i32, long_x, "(");
             win.attron(COLOR_PAIR(if *e {
-                COLOR_PAIR_ENABLED.into()
+                COLOR_PAIR_ENABLED
             } else {
-                COLOR_PAIR_H3.into()
+                COLOR_PAIR_H3
             }));
-            win.mvaddstr(
-                c.y + i as i32,
-                c.x + arg.len() as i32 + arg_d.len() as i32 + 3,
-                arg_l,
-            );
+            put_str(win, c.y + i as i32, long_x + 1, arg_l);
             win.attroff(COLOR_PAIR(if *e {
-                COLOR_PAIR_ENABLED.into()
+                COLOR_PAIR_ENABLED
             } else {
-                COLOR_PAIR_H3.into()
+                COLOR_PAIR_H3
             }));
-            win.mvaddstr(
-                c.y + i as i32,
-                c.x + arg.len() as i32 + arg_d.len() as i32 + 2,
-                "(",
-            );
-            win.mvaddstr(
+            put_str(
+                win,
                 c.y + i as i32,
-                c.x + arg.len() as i32 + arg_d.len() as i32 + arg_l.len() as i32 + 3,
+                long_x + 1 + display_width(arg_l) as i32,
                 ")",
             );
         }
@@ -303,11 +591,10 @@ impl UiElement for ArgList {
     fn size(&self) -> Coord {
         let mut max_width = 0;
         for i in 0..self.args.len() {
-            let width = format!(
+            let width = display_width(&format!(
                 "{} {} ({})",
                 self.args[i], self.arg_descs[i], self.arg_long[i]
-            )
-            .len();
+            ));
             if width > max_width {
                 max_width = width;
             }
@@ -317,6 +604,333 @@ impl UiElement for ArgList {
     }
 }
 
+impl BranchList {
+    fn name_width(&self) -> usize {
+        self.branches
+            .iter()
+            .map(|b| display_width(&b.name))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl UiElement for BranchList {
+    fn new() -> BranchList {
+        BranchList { branches: vec![] }
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        let width = self.name_width();
+        for (i, branch) in self.branches.iter().enumerate() {
+            let y = c.y + i as i32;
+            if branch.head {
+                win.attron(Attribute::Bold);
+                put_str(win, y, c.x, "*");
+            }
+            let c_pair = if branch.remote {
+                COLOR_PAIR_REMOTE
+            } else {
+                COLOR_PAIR_LOCAL
+            };
+            win.attron(COLOR_PAIR(c_pair));
+            put_str(win, y, c.x + 2, &branch.name);
+            win.attroff(COLOR_PAIR(c_pair));
+            win.attroff(Attribute::Bold);
+
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, c.x + 3 + width as i32, &branch.oid);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(
+                win,
+                y,
+                c.x + 4 + (width + display_width(&branch.oid)) as i32,
+                &branch.subject,
+            );
+        }
+    }
+
+    fn size(&self) -> Coord {
+        let width = self.name_width();
+        let max_width = self
+            .branches
+            .iter()
+            .map(|b| width + display_width(&b.oid) + display_width(&b.subject) + 4)
+            .max()
+            .unwrap_or(0);
+        Coord::new(max_width as i32, self.branches.len() as i32)
+    }
+}
+
+impl UiElement for CommitList {
+    fn new() -> CommitList {
+        CommitList { commits: vec![] }
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        for (i, commit) in self.commits.iter().enumerate() {
+            let y = c.y + i as i32;
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, c.x, &commit.oid);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(
+                win,
+                y,
+                c.x + display_width(&commit.oid) as i32 + 1,
+                &commit.subject,
+            );
+        }
+    }
+
+    fn size(&self) -> Coord {
+        let max_width = self
+            .commits
+            .iter()
+            .map(|c| display_width(&c.oid) + 1 + display_width(&c.subject))
+            .max()
+            .unwrap_or(0);
+        Coord::new(max_width as i32, self.commits.len() as i32)
+    }
+}
+
+impl TodoList {
+    /// Columns of the verb, the longest ones with a space after them
+    const VERB_WIDTH: usize = 7;
+}
+
+impl UiElement for TodoList {
+    fn new() -> TodoList {
+        TodoList { lines: vec![] }
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        for (i, line) in self.lines.iter().enumerate() {
+            let y = c.y + i as i32;
+            let c_pair = match line.verb {
+                TodoVerb::Pick => COLOR_PAIR_DEFAULT,
+                TodoVerb::Drop => COLOR_PAIR_REMOVED,
+                _ => COLOR_PAIR_H1,
+            };
+            win.attron(COLOR_PAIR(c_pair));
+            put_str(win, y, c.x, line.verb.name());
+            win.attroff(COLOR_PAIR(c_pair));
+
+            let x = c.x + TodoList::VERB_WIDTH as i32;
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, x, &line.oid);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(
+                win,
+                y,
+                x + display_width(&line.oid) as i32 + 1,
+                &line.subject,
+            );
+        }
+    }
+
+    fn size(&self) -> Coord {
+        let max_width = self
+            .lines
+            .iter()
+            .map(|l| TodoList::VERB_WIDTH + display_width(&l.oid) + 1 + display_width(&l.subject))
+            .max()
+            .unwrap_or(0);
+        Coord::new(max_width as i32, self.lines.len() as i32)
+    }
+}
+
+impl UiElement for StashList {
+    fn new() -> StashList {
+        StashList { stashes: vec![] }
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        for (i, stash) in self.stashes.iter().enumerate() {
+            let y = c.y + i as i32;
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, c.x, &stash.name);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(
+                win,
+                y,
+                c.x + display_width(&stash.name) as i32 + 1,
+                &stash.message,
+            );
+        }
+    }
+
+    fn size(&self) -> Coord {
+        let max_width = self
+            .stashes
+            .iter()
+            .map(|s| display_width(&s.name) + 1 + display_width(&s.message))
+            .max()
+            .unwrap_or(0);
+        Coord::new(max_width as i32, self.stashes.len() as i32)
+    }
+}
+
+impl LogList {
+    /// Columns the commit of `line` takes up after the graph, without the
+    /// author and date
+    fn commit_width(line: &LogLine) -> usize {
+        match &line.commit {
+            Some(commit) => {
+                display_width(&commit.oid)
+                    + commit
+                        .refs
+                        .iter()
+                        .map(|r| display_width(&r.name) + 1)
+                        .sum::<usize>()
+                    + 1
+                    + display_width(&commit.subject)
+            }
+            None => 0,
+        }
+    }
+}
+
+impl UiElement for LogList {
+    fn new() -> LogList {
+        LogList { lines: vec![] }
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        for (i, line) in self.lines.iter().enumerate() {
+            let y = c.y + i as i32;
+            put_str(win, y, c.x, &line.graph);
+            let commit = match &line.commit {
+                Some(commit) => commit,
+                None => continue,
+            };
+
+            let mut x = c.x + display_width(&line.graph) as i32;
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, x, &commit.oid);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+            x += display_width(&commit.oid) as i32 + 1;
+
+            for r in &commit.refs {
+                let c_pair = match r.kind {
+                    RefKind::Head => COLOR_PAIR_H1,
+                    RefKind::Local => COLOR_PAIR_LOCAL,
+                    RefKind::Remote => COLOR_PAIR_REMOTE,
+                    RefKind::Tag => COLOR_PAIR_ENABLED,
+                    RefKind::Other => COLOR_PAIR_DEFAULT,
+                };
+                win.attron(Attribute::Bold);
+                win.attron(COLOR_PAIR(c_pair));
+                put_str(win, y, x, &r.name);
+                win.attroff(COLOR_PAIR(c_pair));
+                win.attroff(Attribute::Bold);
+                x += display_width(&r.name) as i32 + 1;
+            }
+
+            put_str(win, y, x, &commit.subject);
+            x += display_width(&commit.subject) as i32;
+
+            // At the right edge if there's room, after the subject if not
+            let meta = format!("{} {}", commit.author, commit.date);
+            let right = win.get_max_x() - display_width(&meta) as i32;
+            win.attron(COLOR_PAIR(COLOR_PAIR_H3));
+            put_str(win, y, right.max(x + 1), &meta);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_H3));
+        }
+    }
+
+    fn size(&self) -> Coord {
+        let max_width = self
+            .lines
+            .iter()
+            .map(|line| {
+                let meta = line.commit.as_ref().map_or(0, |commit| {
+                    1 + display_width(&commit.author) + 1 + display_width(&commit.date)
+                });
+                display_width(&line.graph) + LogList::commit_width(line) + meta
+            })
+            .max()
+            .unwrap_or(0);
+        Coord::new(max_width as i32, self.lines.len() as i32)
+    }
+}
+
+impl ProgressBar {
+    /// Width of the bar, between the brackets
+    const BAR_WIDTH: usize = 30;
+
+    /// Everything after the label, the bar being drawn separately
+    fn parts(&self) -> Option<(String, Option<f64>, String)> {
+        let progress = self.progress.as_ref()?;
+        let phase = if progress.remote {
+            format!("remote: {}", progress.phase)
+        } else {
+            progress.phase.clone()
+        };
+        let counts = match (progress.fraction(), progress.total) {
+            (Some(fraction), Some(total)) => {
+                format!("{:3.0}% ({}/{})", fraction * 100.0, progress.current, total)
+            }
+            _ => progress.current.to_string(),
+        };
+        Some((phase, progress.fraction(), counts))
+    }
+}
+
+impl UiElement for ProgressBar {
+    fn new() -> ProgressBar {
+        ProgressBar {
+            label: String::new(),
+            progress: None,
+        }
+    }
+
+    fn render(&self, win: &pancurses::Window, c: Coord) {
+        win.attron(Attribute::Bold);
+        win.attron(COLOR_PAIR(COLOR_PAIR_SELECTED));
+        put_str(win, c.y, c.x, &self.label);
+        win.attroff(COLOR_PAIR(COLOR_PAIR_SELECTED));
+        win.attroff(Attribute::Bold);
+
+        let (phase, fraction, counts) = match self.parts() {
+            Some(parts) => parts,
+            None => return,
+        };
+        let mut x = c.x + display_width(&self.label) as i32 + 1;
+        put_str(win, c.y, x, &phase);
+        x += display_width(&phase) as i32 + 1;
+
+        if let Some(fraction) = fraction {
+            let filled = (fraction * ProgressBar::BAR_WIDTH as f64).round() as usize;
+            put_str(win, c.y, x, "[");
+            win.attron(COLOR_PAIR(COLOR_PAIR_ENABLED));
+            put_str(win, c.y, x + 1, "#".repeat(filled));
+            win.attroff(COLOR_PAIR(COLOR_PAIR_ENABLED));
+            put_str(
+                win,
+                c.y,
+                x + 1 + filled as i32,
+                format!("{}]", " ".repeat(ProgressBar::BAR_WIDTH - filled)),
+            );
+            x += ProgressBar::BAR_WIDTH as i32 + 3;
+        }
+        put_str(win, c.y, x, &counts);
+    }
+
+    fn size(&self) -> Coord {
+        let width = match self.parts() {
+            Some((phase, fraction, counts)) => {
+                let bar = if fraction.is_some() {
+                    ProgressBar::BAR_WIDTH + 3
+                } else {
+                    0
+                };
+                display_width(&self.label) + 1 + display_width(&phase) + 1 + bar + counts.len()
+            }
+            None => display_width(&self.label),
+        };
+        Coord::new(width as i32, 1)
+    }
+}
+
 impl KeyList {
     pub fn push_key(&mut self, key: &str, desc: &str) {
         self.keys.push(String::from(key));
@@ -334,17 +948,22 @@ impl UiElement for KeyList {
 
     fn render(&self, win: &pancurses::Window, c: Coord) {
         for (i, key, desc) in izip!(0..self.keys.len(), &self.keys, &self.descs) {
-            win.attron(COLOR_PAIR(COLOR_PAIR_UNTRACKED.into()));
-            win.mvaddstr(c.y + i as i32, c.x, &key);
-            win.attroff(COLOR_PAIR(COLOR_PAIR_UNTRACKED.into()));
-            win.mvaddstr(c.y + i as i32, c.x + key.len() as i32 + 1, &desc);
+            win.attron(COLOR_PAIR(COLOR_PAIR_UNTRACKED));
+            put_str(win, c.y + i as i32, c.x, key);
+            win.attroff(COLOR_PAIR(COLOR_PAIR_UNTRACKED));
+            put_str(
+                win,
+                c.y + i as i32,
+                c.x + display_width(key) as i32 + 1,
+                desc,
+            );
         }
     }
 
     fn size(&self) -> Coord {
         let mut max_width = 0;
         for i in 0..self.keys.len() {
-            let width = format!("{} {}", self.keys[i], self.descs[i]).len();
+            let width = display_width(&format!("{} {}", self.keys[i], self.descs[i]));
             if width > max_width {
                 max_width = width;
             }
@@ -383,7 +1002,7 @@ impl UiElement for Text {
     }
 
     fn render(&self, win: &pancurses::Window, c: Coord) {
-        win.attron(COLOR_PAIR(self.c_pair.into()));
+        win.attron(COLOR_PAIR(self.c_pair));
         if self.bold() {
             win.attron(Attribute::Bold);
         }
@@ -394,9 +1013,9 @@ impl UiElement for Text {
             win.attron(Attribute::Underline);
         }
 
-        win.mvaddstr(c.y, c.x, &self.content);
+        put_str(win, c.y, c.x, &self.content);
 
-        win.attroff(COLOR_PAIR(self.c_pair.into()));
+        win.attroff(COLOR_PAIR(self.c_pair));
         if self.bold() {
             win.attroff(Attribute::Bold);
         }
@@ -409,7 +1028,10 @@ impl UiElement for Text {
     }
 
     fn size(&self) -> Coord {
-        Coord::new(self.content.len() as i32, (self.content.len() > 0) as i32)
+        Coord::new(
+            display_width(&self.content) as i32,
+            (!self.content.is_empty()) as i32,
+        )
     }
 }
 
@@ -441,7 +1063,7 @@ impl UiElement for ListHeader {
     }
 
     fn size(&self) -> Coord {
-        Coord::new((self.title.size().x + self.amount.size().x + 1) as i32, 1)
+        Coord::new(self.title.size().x + self.amount.size().x + 1, 1)
     }
 }
 